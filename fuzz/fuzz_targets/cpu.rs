@@ -0,0 +1,68 @@
+#![no_main]
+
+//! Runs arbitrary ROMs with arbitrary key inputs through the CPU.
+//! Every run must end in `Ok` or a `Chip8Error`, never in a panic
+//!
+//! Input layout:
+//! - byte 0: quirks, bit 0 enables `key-wait-press`
+//! - byte 1: instructions per frame, from 1 to 64
+//! - bytes 2-3: size of the ROM, big-endian, cut to the bytes left
+//! - the ROM
+//! - the key events, one per frame: the low nibble is the key, bit 4 set if pressed
+
+use libfuzzer_sys::fuzz_target;
+
+use yac8e::cpu::{self, Cpu, PROGRAM_START};
+use yac8e::keypad::{KeyEvent, Keypad};
+use yac8e::quirks::Quirks;
+use yac8e::ram::Ram;
+use yac8e::screen::Screen;
+
+use std::time::Instant;
+
+/// Maximum number of instructions run for an input
+const MAX_INSTRUCTIONS: u64 = 100_000;
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < 4 {
+        return;
+    }
+    let quirks = Quirks {
+        key_wait_press: data[0] & 0x1 != 0,
+    };
+    let instructions_per_frame = (data[1] % 64) as u64 + 1;
+    let rom_size = (((data[2] as usize) << 8) | data[3] as usize).min(data.len() - 4);
+    let (rom, inputs) = data[4..].split_at(rom_size);
+
+    // The RNG is seeded so a crash can be replayed
+    let mut cpu = Cpu::new(quirks, cpu::new_rng(Some(0)));
+    let mut ram = Ram::new();
+    let mut screen = Screen::new();
+    let mut keypad = Keypad::new();
+    if ram.write_bytes(PROGRAM_START as usize, rom).is_err() {
+        return;
+    }
+
+    let mut inputs = inputs.iter();
+    for instruction in 0..MAX_INSTRUCTIONS {
+        if instruction % instructions_per_frame == 0 {
+            if let Some(&input) = inputs.next() {
+                keypad.push_events(vec![KeyEvent {
+                    key: input & 0xF,
+                    pressed: input & 0x10 != 0,
+                    time: Instant::now(),
+                }]);
+            }
+            keypad.apply_events();
+        }
+
+        if cpu.run_instruction(&mut ram, &mut screen, &mut keypad, false).is_err() {
+            return;
+        }
+
+        if (instruction + 1) % instructions_per_frame == 0 {
+            cpu.update_timers(false);
+            keypad.update_tested();
+        }
+    }
+});
@@ -1,14 +1,62 @@
-use crate::cpu::{Cpu, PROGRAM_START};
-use crate::ram::Ram;
-use crate::screen::Screen;
-use crate::keypad::Keypad;
-use crate::handler::{Handler, HandlerType};
-use crate::utils::log_debug;
+use crate::cpu::{self, Cpu, PROGRAM_START};
+use crate::ram::{Ram, MEMORY_SIZE};
+use crate::screen::{Screen, WIDTH, HEIGHT};
+use crate::keypad::{Keypad, WaitState};
+use crate::handler::{Handler, HandlerType, Bindings, Command, SoundTrait, ToneConfig, WavRecorder, WINDOW_TITLE};
+use crate::scaler::ScalerType;
+use crate::filters::FilterPipeline;
+use crate::config::{ConfigFile, RomArguments};
+use crate::frame::Frame;
+use crate::frame_timer::FrameTimer;
+use crate::launcher::Launcher;
+use crate::rom_watcher::RomWatcher;
+use crate::osd::{Osd, OsdMode};
+use crate::keypad_view::{self, draw_keypad};
+use crate::virtual_keypad::{VirtualKeypad, VirtualKeypadMode};
+use crate::quirks::Quirks;
+use crate::sanitizer::{Sanitizer, StrictMode};
+use crate::input_layer::{Autofire, InputLayer};
+use crate::error::Chip8Error;
+use crate::utils::{log_debug, log_info, log_warning};
 
 use std::error::Error;
 use std::fs::File;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 use std::io::Read;
+use std::path::Path;
+use std::mem;
+
+/// Volume change for each volume hotkey press
+const VOLUME_STEP: f32 = 0.05;
+/// File types shown by the open ROM dialog
+const ROM_PATTERNS: [&str; 3] = ["*.ch8", "*.c8", "*.rom"];
+/// WAV file the sound is recorded into when the recording is started with the hotkey and no file is set
+const DEFAULT_RECORDING_FILE: &str = "yac8e.wav";
+/// Prefix of the screenshot files, numbered from 1
+const SCREENSHOT_PREFIX: &str = "yac8e_";
+/// Number of frames per second, which is also the rate of the delay and sound timers
+pub const FRAME_RATE: f64 = 60.0;
+/// Emulated duration of a frame
+const FRAME_DURATION: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+#[derive(Debug)]
+/// Saved state of the VM, restored with the load state hotkey
+struct SavedState {
+    /// Saved CPU
+    cpu: Cpu,
+    /// Saved RAM
+    ram: Ram,
+    /// Saved screen
+    screen: Screen,
+    /// Saved `FX0A` wait
+    wait_state: WaitState,
+    /// Emulated time elapsed since the start of the VM
+    emulated_time: Duration,
+    /// Number of frames emulated since the start of the VM
+    frames: u32,
+    /// Number of instructions already run in the current frame
+    frame_instructions: u32,
+}
 
 #[derive(Debug)]
 /// The main struct containing all the components of the CHIP-8 VM
@@ -25,104 +73,933 @@ pub struct Chip8 {
     screen: Screen,
     /// Keypad of the VM
     keypad: Keypad,
+    /// Data of the loaded ROM, reloaded on reset
+    rom_data: Vec<u8>,
+    /// Timer pacing the main loop
+    frame_timer: FrameTimer,
+    /// ROM launcher, `None` if a ROM was given at startup
+    launcher: Option<Launcher>,
+    /// Whether the launcher is shown instead of the running ROM
+    in_launcher: bool,
+    /// Watcher of the running ROM file, `None` if the ROM isn't watched
+    watcher: Option<RomWatcher>,
+    /// On-screen display drawn over the screen
+    osd: Osd,
+    /// Whether the keypad is drawn over the screen
+    show_keypad: bool,
+    /// Clickable keypad drawn beside or under the screen
+    virtual_keypad: VirtualKeypad,
+    /// Autofire and macros, between the held keys and the keys seen by the ROM
+    input_layer: InputLayer,
+    /// State saved with the save state hotkey, dropped when a ROM is loaded
+    saved_state: Option<SavedState>,
+    /// Whether the window is fullscreen
+    fullscreen: bool,
+    /// Current volume of the beep, changed with hotkeys
+    volume: f32,
+    /// Whether the sound is muted
+    muted: bool,
+    /// Emulated time elapsed since the start of the VM
+    emulated_time: Duration,
+    /// Number of frames emulated since the start of the VM
+    frames: u32,
+    /// Number of instructions already run in the current frame
+    frame_instructions: u32,
+    /// Number of instructions run since the start of the VM
+    instructions: u64,
+    /// Whether the emulation is paused
+    paused: bool,
+    /// Whether the emulation is paused because the window lost the focus, independently of `paused`
+    focus_paused: bool,
+    /// Number of instructions run in each frame, changed with hotkeys
+    instructions_per_frame: u32,
+    /// Whether the fast-forward key is held
+    fast_forward_held: bool,
+    /// Whether the fast-forward is turned on
+    fast_forward: bool,
+    /// Whether the slow motion is turned on
+    slow_motion: bool,
+    /// Whether the frame must be presented even if the screen didn't change, e.g. because an overlay changed
+    redraw: bool,
+    /// Whether the beep is currently playing
+    beeping: bool,
+    /// Sound recorder, `None` when not recording
+    recorder: Option<WavRecorder>,
+    /// Number of recordings started, used to number the WAV files
+    recordings: u32,
 }
 
 #[derive(Clone, Debug)]
 /// A config struct containing the CHIP-8 configuration
 pub struct Chip8Config {
-    /// ROM path
-    pub rom: String,
+    /// ROM path, the launcher is shown if `None`
+    pub rom: Option<String>,
+    /// Directory browsed by the launcher
+    pub rom_dir: String,
+    /// Whether the ROM is reloaded and the VM reset when the ROM file changes
+    pub watch: bool,
+    /// Whether the emulation is paused and the sound muted while the window doesn't have the focus
+    pub auto_pause: bool,
     /// Debugging output
     pub debug: bool,
+    /// Behaviors differing between interpreters
+    pub quirks: Quirks,
+    /// Seed of the random generator, for a deterministic emulation. The instructions per frame can't be changed then
+    pub seed: Option<u64>,
+    /// What the strict mode does with the behaviors real hardware wouldn't tolerate
+    pub strict: StrictMode,
+    /// Whether the RAM is filled with random bytes before loading the ROM, like on real hardware
+    pub random_ram: bool,
     /// Handler type for display, keyboard and sound
     pub handler_type: HandlerType,
-    /// Hertz value for CPU cycles per sec speed
-    pub hertz: f64,
+    /// Number of instructions run in each 60 Hz frame
+    pub instructions_per_frame: u32,
+    /// Whether the frames are presented with vsync, if the backend supports it
+    pub vsync: bool,
+    /// Speed multiplier of the fast-forward, uncapped if `None`
+    pub fast_forward: Option<f64>,
+    /// Speed multiplier of the slow motion
+    pub slow_motion: f64,
     /// Display window width
     pub window_width: usize,
     /// Display window height
     pub window_height: usize,
+    /// Upscaling filter applied to the screen before displaying it
+    pub scaler: ScalerType,
+    /// Post-processing filters applied to the upscaled screen
+    pub filters: FilterPipeline,
+    /// What the on-screen display shows at startup
+    pub osd: OsdMode,
+    /// Whether the keypad is drawn over the screen at startup
+    pub keypad_overlay: bool,
+    /// Where the clickable keypad is drawn, the window being enlarged to make room for it
+    pub virtual_keypad: VirtualKeypadMode,
+    /// Keys pressed and released automatically while held
+    pub autofire: Autofire,
+    /// Hotkeys of the emulator commands
+    pub bindings: Bindings,
+    /// SDL video driver (e.g. `x11`, `wayland`, or `dummy` / `offscreen` without any display), SDL's choice if `None`
+    pub video_driver: Option<String>,
+    /// Settings of the beep
+    pub tone: ToneConfig,
+    /// WAV file the sound is recorded into, recording from the start if set
+    pub record_audio: Option<String>,
+    /// Config file, read again for the section of each ROM opened
+    pub config_file: Option<String>,
+    /// Command line values of the settings following the ROM (quirks, instructions per frame, filters)
+    pub rom_arguments: RomArguments,
 }
 
 impl Chip8 {
     /// Creates a new `Chip8` object given a `Chip8Config`
     pub fn new(config: Chip8Config) -> Result<Self, Box<dyn Error>> {
+        // The window gets room for the virtual keypad, the screen keeps the configured size
+        let virtual_keypad = VirtualKeypad::new(config.virtual_keypad, config.window_width, config.window_height);
+        let (window_width, window_height) = virtual_keypad.window_size();
+        let handler = Handler::new(Chip8Config {
+            window_width: window_width,
+            window_height: window_height,
+            ..config.clone()
+        })?;
+        let frame_timer = FrameTimer::new(FRAME_RATE, handler.display.vsync());
+        let osd = Osd::new(config.osd);
+        let cpu = Cpu::new(config.quirks, cpu::new_rng(config.seed));
+        let input_layer = InputLayer::new(config.autofire.clone());
+
         Ok(Chip8 {
-            config: config.clone(),
-            handler: Handler::new(config)?,
-            cpu: Cpu::new(),
+            volume: config.tone.volume,
+            muted: false,
+            show_keypad: config.keypad_overlay,
+            instructions_per_frame: config.instructions_per_frame,
+            config: config,
+            handler: handler,
+            cpu: cpu,
             ram: Ram::new(),
             screen: Screen::new(),
             keypad: Keypad::new(),
+            rom_data: Vec::new(),
+            frame_timer: frame_timer,
+            launcher: None,
+            in_launcher: false,
+            watcher: None,
+            osd: osd,
+            virtual_keypad: virtual_keypad,
+            input_layer: input_layer,
+            saved_state: None,
+            fullscreen: false,
+            emulated_time: Duration::new(0, 0),
+            frames: 0,
+            frame_instructions: 0,
+            instructions: 0,
+            paused: false,
+            focus_paused: false,
+            fast_forward_held: false,
+            fast_forward: false,
+            slow_motion: false,
+            redraw: true,
+            beeping: false,
+            recorder: None,
+            recordings: 0,
         })
     }
 
-    /// Runs a ROM given a `Chip8Config`
+    /// Runs a ROM given a `Chip8Config`, or the launcher if no ROM is given
     pub fn run_rom(chip8_config: Chip8Config) -> Result<(), Box<dyn Error>> {
         let mut chip8 = Chip8::new(chip8_config)?;
 
-        // Reads ROM data
-        let mut file = File::open(&chip8.config.rom)?;
-        let mut data = Vec::new();
-        file.read_to_end(&mut data)?;
-
-        chip8.load_rom(&data)?;
+        match chip8.config.rom.clone() {
+            Some(rom) => {
+                let data = Chip8::read_rom(&rom)?;
+                chip8.load_rom(&data)?;
+                if chip8.config.watch {
+                    chip8.watcher = Some(RomWatcher::new(&rom));
+                }
+            },
+            None => {
+                chip8.launcher = Some(Launcher::new(&chip8.config.rom_dir, chip8.config.instructions_per_frame));
+                chip8.in_launcher = true;
+            },
+        }
 
-        let mut accumulator = Duration::new(0, 0);
-        let mut prev_time = Instant::now();
-        let delta_cap = Duration::from_millis(3000);
+        if chip8.config.record_audio.is_some() {
+            chip8.start_recording();
+        }
+        chip8.update_title()?;
 
-        let timer_frequency = Duration::from_secs_f64(1.0 / 60.0);
+        // The recording is finished whichever way the loop ends, so the WAV file is always valid
+        let result = chip8.run_loop();
+        chip8.stop_recording();
+        result
+    }
 
+    /// Runs the main loop, until the window is closed or the emulator quits
+    fn run_loop(&mut self) -> Result<(), Box<dyn Error>> {
         loop {
-            // https://www.reddit.com/r/EmuDev/comments/7v7flo/duncetier_chip8_question_how_do_i_set_the_timers/
-            let current_time = Instant::now();
-            let mut delta = current_time - prev_time;
-            // "Cap" the delta value in case the program gets stuck (e.g: waiting for a keystroke) so we don't have to simulate this long wait
-            if delta > delta_cap {
-                delta = delta_cap.clone();
+            // Waits for the next frame. If the host fell behind or runs faster, several frames are emulated
+            // but only the last one is presented
+            let frames = self.frame_timer.wait();
+
+            // We queue the key events of the keyboard and the virtual keypad, they are applied to the keys state
+            // by the emulated frames. Then we run the commands triggered by the hotkeys
+            let mut events = Vec::new();
+            let commands = self.handler.keyboard.poll_events(&mut events);
+            let pointers = self.handler.keyboard.pointers();
+            self.virtual_keypad.update(&pointers, &mut events);
+            self.keypad.push_events(events);
+            if commands.contains(&Command::Close) {
+                break;
+            }
+            // The focus is tracked in the launcher too, so the emulation is paused if a ROM is launched unfocused
+            for command in commands.iter() {
+                match command {
+                    Command::FocusLost => self.set_focused(false)?,
+                    Command::FocusGained => self.set_focused(true)?,
+                    _ => {},
+                }
             }
-            prev_time = current_time;
-            accumulator += delta;
 
-            // With this, timers run at 60 Hz while the rest of the program can run at any speed
-            while accumulator >= timer_frequency {
-                let active_sound = chip8.cpu.update_timers(chip8.config.debug);
-                accumulator -= timer_frequency;
+            if self.in_launcher {
+                if self.run_launcher(commands, frames)? {
+                    break;
+                }
+                continue;
+            }
 
-                if active_sound {
-                    chip8.handler.sound.play_beep();
+            // Going back to the launcher if the ROM was launched from it
+            if commands.contains(&Command::Quit) {
+                if self.launcher.is_none() {
+                    break;
                 }
-                else {
-                    chip8.handler.sound.stop_beep();
+                self.show_launcher()?;
+                continue;
+            }
+            for command in commands {
+                self.run_command(command)?;
+            }
+
+            // Reloads the ROM if it was rewritten
+            let rom_changed = match &mut self.watcher {
+                Some(watcher) => watcher.poll(),
+                None => false,
+            };
+            if rom_changed {
+                self.reload_rom()?;
+            }
+
+            // While paused, the loop keeps its pace to stay responsive but no frame is emulated
+            if !self.paused && !self.focus_paused {
+                let result = self.run_frames(frames);
+                self.halt_on_violation(result)?;
+                // The timers shown by the stats change every frame
+                if self.osd.shows_stats() {
+                    self.redraw = true;
                 }
             }
+            if self.osd.update(self.frames as u64, self.instructions) {
+                self.redraw = true;
+            }
+            // The keypad shows the keys state, which may change even while paused
+            if self.show_keypad || self.virtual_keypad.is_enabled() {
+                self.redraw = true;
+            }
+
+            // If the screen or an overlay changed, upscales and filters the screen, draws the overlays,
+            // then presents it once for all the frames emulated
+            if self.screen.take_dirty() || self.redraw {
+                let frame = self.config.scaler.scale(&self.screen.pixels, WIDTH, HEIGHT);
+                let mut frame = self.config.filters.apply(frame, self.config.window_width, self.config.window_height);
+                self.draw_overlays(&mut frame);
+                self.present(&frame)?;
+                self.redraw = false;
+            }
+            else {
+                self.handler.display.idle()?;
+            }
+        }
 
-            // We update the keys state (released / pressed), returns false if we receive an exit signal (Esc or Quit)
-            if !chip8.handler.keyboard.update_keys_state(&mut chip8.keypad.keys_state) {
+        Ok(())
+    }
+
+    /// Runs the launcher for `frames` frames: navigation, preview and display.
+    /// Launches the ROM chosen, if any. Returns whether the emulator must quit
+    fn run_launcher(&mut self, commands: Vec<Command>, frames: u32) -> Result<bool, Box<dyn Error>> {
+        self.keypad.apply_all_events();
+        let launcher = match &mut self.launcher {
+            Some(launcher) => launcher,
+            None => return Ok(false),
+        };
+
+        let mut rom = None;
+        for command in commands {
+            rom = match command {
+                Command::Quit => return Ok(true),
+                Command::LoadRom(path) => Some(path),
+                command => launcher.run_command(&command),
+            };
+            if rom.is_some() {
                 break;
             }
+        }
+        if rom.is_none() {
+            rom = launcher.update(&self.keypad.keys_state, frames);
+        }
+
+        match rom {
+            Some(rom) => self.launch(&rom)?,
+            None => {
+                let frame = launcher.draw(self.config.window_width, self.config.window_height);
+                self.present(&frame)?;
+            },
+        }
+        Ok(false)
+    }
+
+    /// Presents `frame`, with the virtual keypad beside or under it if enabled
+    fn present(&mut self, frame: &Frame) -> Result<(), Box<dyn Error>> {
+        if self.virtual_keypad.is_enabled() {
+            let frame = self.virtual_keypad.compose(frame, &self.keypad);
+            return self.handler.display.update(&frame.pixels, frame.width, frame.height);
+        }
+        self.handler.display.update(&frame.pixels, frame.width, frame.height)
+    }
+
+    /// Runs the ROM at `path`, and adds it to the recent ROMs of the launcher if there is one
+    fn launch(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
+        if self.open_rom(path)? {
+            self.in_launcher = false;
+            self.redraw = true;
+            if let Some(launcher) = &mut self.launcher {
+                launcher.add_recent(path);
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops the running ROM and shows the launcher
+    fn show_launcher(&mut self) -> Result<(), Box<dyn Error>> {
+        self.silence_beep();
+        self.paused = false;
+        self.in_launcher = true;
+        self.update_title()
+    }
+
+    /// Runs an emulator command triggered by a hotkey
+    fn run_command(&mut self, command: Command) -> Result<(), Box<dyn Error>> {
+        match command {
+            // Handled by the main loop and the launcher
+            Command::Quit | Command::Close | Command::FocusLost | Command::FocusGained => {},
+            Command::MenuUp | Command::MenuDown | Command::MenuSelect | Command::MenuBack => {},
+            Command::ToggleMute => {
+                self.muted = !self.muted;
+                self.handler.sound.set_muted(self.muted || self.focus_paused);
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.set_muted(self.muted);
+                }
+                self.notify(if self.muted { "Sound muted" } else { "Sound unmuted" });
+            },
+            Command::VolumeUp | Command::VolumeDown => {
+                let step = if command == Command::VolumeUp { VOLUME_STEP } else { -VOLUME_STEP };
+                self.volume = (self.volume + step).clamp(0.0, 1.0);
+                self.handler.sound.set_volume(self.volume);
+                if let Some(recorder) = &mut self.recorder {
+                    recorder.set_volume(self.volume);
+                }
+                self.notify(format!("Volume: {:.0}%", self.volume * 100.0));
+            },
+            Command::ToggleRecording => {
+                if self.recorder.is_some() {
+                    self.stop_recording();
+                }
+                else {
+                    self.start_recording();
+                }
+            },
+            Command::TogglePause => {
+                self.paused = !self.paused;
+                if self.paused {
+                    self.silence_beep();
+                }
+                self.notify(if self.paused { "Paused" } else { "Resumed" });
+                self.update_title()?;
+            },
+            Command::FrameAdvance | Command::StepInstruction => {
+                if !self.paused {
+                    return Ok(());
+                }
+                if command == Command::FrameAdvance {
+                    let result = self.run_frame();
+                    self.halt_on_violation(result)?;
+                }
+                else {
+                    let result = self.step_instruction();
+                    self.halt_on_violation(result)?;
+                    self.notify(format!("PC: {:#05X}", self.cpu.pc()));
+                }
+                // The beep would go on until the emulation resumes
+                self.silence_beep();
+            },
+            Command::HoldFastForward | Command::ReleaseFastForward => {
+                self.fast_forward_held = command == Command::HoldFastForward;
+                self.update_speed()?;
+            },
+            Command::ToggleFastForward => {
+                self.fast_forward = !self.fast_forward;
+                self.update_speed()?;
+            },
+            Command::ToggleSlowMotion => {
+                self.slow_motion = !self.slow_motion;
+                self.update_speed()?;
+            },
+            Command::SpeedUp | Command::SpeedDown => {
+                // The timers must tick after the same number of instructions in every run
+                if self.config.seed.is_some() {
+                    self.notify("Instructions per frame locked by the seed");
+                    return Ok(());
+                }
+                // Steps of about 10%, so the speed can be finely tuned at low and high instructions per frame
+                let step = (self.instructions_per_frame / 10).max(1);
+                self.instructions_per_frame = if command == Command::SpeedUp {
+                    self.instructions_per_frame.saturating_add(step)
+                }
+                else {
+                    self.instructions_per_frame.saturating_sub(step).max(1)
+                };
+                self.update_speed()?;
+            },
+            Command::Reset => {
+                self.reset()?;
+                self.notify("Reset");
+            },
+            Command::ToggleOsd => {
+                let text = match self.osd.cycle_mode() {
+                    OsdMode::OFF => "OSD off",
+                    OsdMode::MESSAGES => "OSD: messages",
+                    OsdMode::STATS => "OSD: messages and stats",
+                };
+                self.notify(text);
+                self.redraw = true;
+            },
+            Command::ToggleKeypadOverlay => {
+                self.show_keypad = !self.show_keypad;
+                self.redraw = true;
+            },
+            Command::ToggleMacroRecording => {
+                if self.input_layer.toggle_recording() {
+                    self.notify("Recording a macro");
+                }
+                else {
+                    self.notify(format!("Macro recorded: {} frames", self.input_layer.macro_frames()));
+                }
+            },
+            Command::PlayMacro => {
+                if self.input_layer.toggle_playback() {
+                    self.notify("Playing the macro");
+                }
+                else if self.input_layer.macro_frames() == 0 {
+                    self.notify("No macro recorded");
+                }
+                else {
+                    self.notify("Macro stopped");
+                }
+            },
+            Command::SaveState => {
+                self.saved_state = Some(SavedState {
+                    cpu: self.cpu.clone(),
+                    ram: self.ram.clone(),
+                    screen: self.screen.clone(),
+                    wait_state: self.keypad.wait_state(),
+                    emulated_time: self.emulated_time,
+                    frames: self.frames,
+                    frame_instructions: self.frame_instructions,
+                });
+                self.notify("State saved");
+            },
+            Command::LoadState => {
+                match self.saved_state.take() {
+                    Some(state) => {
+                        // The beep restarts with the next instruction if the saved sound timer is active
+                        self.silence_beep();
+                        self.advance_recording();
+                        self.cpu = state.cpu.clone();
+                        self.ram = state.ram.clone();
+                        self.screen = state.screen.clone();
+                        self.keypad.restore_wait_state(state.wait_state);
+                        self.emulated_time = state.emulated_time;
+                        self.frames = state.frames;
+                        self.frame_instructions = state.frame_instructions;
+                        // The recording goes on from the restored emulated time, the sound component resynchronizes itself
+                        if let Some(recorder) = &mut self.recorder {
+                            recorder.seek(self.emulated_time);
+                        }
+                        self.saved_state = Some(state);
+                        self.notify("State loaded");
+                    },
+                    None => self.notify("No state saved"),
+                }
+            },
+            Command::Screenshot => {
+                self.save_screenshot();
+            },
+            Command::ToggleFullscreen => {
+                match self.handler.display.set_fullscreen(!self.fullscreen) {
+                    Ok(()) => self.fullscreen = !self.fullscreen,
+                    Err(e) => log_warning(format!("Unable to switch to fullscreen: {}", e)),
+                }
+                self.redraw = true;
+            },
+            Command::ToggleDebug => {
+                self.config.debug = !self.config.debug;
+                self.notify(if self.config.debug { "Debugging output on" } else { "Debugging output off" });
+            },
+            Command::OpenRom => {
+                let dir = match &self.config.rom {
+                    Some(rom) => Path::new(rom).parent().and_then(|p| p.to_str()).unwrap_or(""),
+                    None => &self.config.rom_dir,
+                };
+                let filter = (&ROM_PATTERNS[..], "CHIP-8 ROMs");
+                if let Some(path) = tinyfiledialogs::open_file_dialog("Open a CHIP-8 ROM", dir, Some(filter)) {
+                    self.launch(&path)?;
+                }
+            },
+            Command::LoadRom(path) => {
+                self.launch(&path)?;
+            },
+        }
+
+        Ok(())
+    }
+
+    /// Reads the ROM data from the file at `path`
+    fn read_rom(path: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        let mut file = File::open(path)?;
+        let mut data = Vec::new();
+        file.read_to_end(&mut data)?;
+
+        Ok(data)
+    }
+
+    /// Replaces the running ROM by the ROM at `path`, then resets the VM.
+    /// The running ROM keeps running if the new one can't be loaded.
+    /// Returns whether the ROM was loaded
+    fn open_rom(&mut self, path: &str) -> Result<bool, Box<dyn Error>> {
+        let data = match Chip8::read_rom(path) {
+            // Checks that the ROM fits in memory before replacing the running one
+            Ok(data) => Chip8::check_rom(&data).map(|_| data).map_err(|e| e.into()),
+            Err(e) => Err(e),
+        };
+        match data {
+            Ok(data) => {
+                self.apply_rom_settings(path);
+                self.config.rom = Some(path.to_string());
+                self.rom_data = data;
+                self.saved_state = None;
+                if self.config.watch {
+                    self.watcher = Some(RomWatcher::new(path));
+                }
+                self.reset()?;
+                self.notify(format!("Running {}", path));
+                self.update_title()?;
+                Ok(true)
+            },
+            Err(e) => {
+                log_warning(format!("Unable to load the ROM {}: {}", path, e));
+                Ok(false)
+            },
+        }
+    }
+
+    /// Resolves the settings following the ROM at `path` from its section of the config file, before it is run.
+    /// The current settings are kept if the config file can't be read or is invalid
+    fn apply_rom_settings(&mut self, path: &str) {
+        let config = match &self.config.config_file {
+            Some(file) => ConfigFile::load(Path::new(file), path),
+            None => Ok(ConfigFile::default()),
+        };
+        match config.and_then(|config| self.config.rom_arguments.resolve(&config)) {
+            Ok(settings) => {
+                // The speed changed with the hotkeys is kept, unless the ROM has another speed
+                if settings.instructions_per_frame != self.config.instructions_per_frame {
+                    self.instructions_per_frame = settings.instructions_per_frame;
+                }
+                self.config.quirks = settings.quirks;
+                self.config.instructions_per_frame = settings.instructions_per_frame;
+                self.config.filters = settings.filters;
+            },
+            Err(e) => log_warning(format!("Unable to read the settings of {}: {}", path, e)),
+        }
+    }
+
+    /// Reloads the running ROM from its file after it changed, then resets the VM.
+    /// The window, and so its position, and the emulator settings are kept,
+    /// the settings following the ROM being read again from the config file
+    fn reload_rom(&mut self) -> Result<(), Box<dyn Error>> {
+        if let Some(rom) = self.config.rom.clone() {
+            self.notify(format!("{} changed, reloading it", rom));
+            self.open_rom(&rom)?;
+        }
+        Ok(())
+    }
+
+    /// Resets the VM: new CPU, RAM (with the fonts and the ROM reloaded), screen and keypad.
+    /// The window, the sound and the emulator settings are kept
+    pub fn reset(&mut self) -> Result<(), Chip8Error> {
+        self.silence_beep();
+        self.cpu = Cpu::new(self.config.quirks, cpu::new_rng(self.config.seed));
+        self.ram = Ram::new();
+        self.screen = Screen::new();
+        self.keypad = Keypad::new();
+        self.frame_instructions = 0;
+
+        let data = mem::take(&mut self.rom_data);
+        self.load_rom(&data)
+    }
+
+    /// Speed multiplier of the emulation, `None` if uncapped. Fast-forward takes precedence over slow motion
+    fn speed(&self) -> Option<f64> {
+        if self.fast_forward || self.fast_forward_held {
+            self.config.fast_forward
+        }
+        else if self.slow_motion {
+            Some(self.config.slow_motion)
+        }
+        else {
+            Some(1.0)
+        }
+    }
+
+    /// Describes the current speed, e.g. `x4 - 10 IPF`
+    fn speed_text(&self) -> String {
+        let multiplier = match self.speed() {
+            Some(speed) => format!("x{}", speed),
+            None => "Max speed".to_string(),
+        };
+        format!("{} - {} IPF", multiplier, self.instructions_per_frame)
+    }
+
+    /// Applies a speed change, and shows the new speed
+    fn update_speed(&mut self) -> Result<(), Box<dyn Error>> {
+        self.frame_timer.set_speed(self.speed());
+        let text = format!("Speed: {}", self.speed_text());
+        self.notify(text);
+        self.update_title()
+    }
+
+    /// Shows the state of the emulation in the window title
+    fn update_title(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.in_launcher {
+            return self.handler.display.set_title(&format!("{} - ROM launcher", WINDOW_TITLE));
+        }
+
+        let rom = self.config.rom.as_ref()
+            .and_then(|rom| Path::new(rom).file_name())
+            .map(|f| f.to_string_lossy())
+            .unwrap_or_default();
+        let mut title = format!("{} - {} - {}", WINDOW_TITLE, rom, self.speed_text());
+        if self.paused || self.focus_paused {
+            title.push_str(" - Paused");
+        }
+        self.handler.display.set_title(&title)
+    }
+
+    /// Pauses the emulation and mutes the sound while the window doesn't have the focus, if enabled.
+    /// A pause with the hotkey is kept when the focus comes back
+    fn set_focused(&mut self, focused: bool) -> Result<(), Box<dyn Error>> {
+        if !self.config.auto_pause || self.focus_paused != focused {
+            return Ok(());
+        }
+        self.focus_paused = !focused;
+        if self.focus_paused {
+            self.silence_beep();
+        }
+        self.handler.sound.set_muted(self.muted || self.focus_paused);
+        log_info(if self.focus_paused { "Window inactive, paused" } else { "Window active, resumed" });
+        self.update_title()
+    }
+
+    /// Logs `text` and shows it on the OSD
+    fn notify<T: AsRef<str>>(&mut self, text: T) {
+        log_info(text.as_ref());
+        self.osd.notify(text);
+        self.redraw = true;
+    }
+
+    /// Draws the overlays on top of the displayed frame: the keypad if turned on, then the OSD
+    /// (the messages, the speed when it isn't the normal one, and the stats if turned on)
+    fn draw_overlays(&self, frame: &mut Frame) {
+        if self.show_keypad {
+            let scale = (frame.height / (HEIGHT * 5)).max(1);
+            let size = keypad_view::keypad_size(scale);
+            let x = frame.width.saturating_sub(size + 2 * scale);
+            let y = frame.height.saturating_sub(size + 2 * scale);
+            draw_keypad(frame, x, y, &self.keypad, scale);
+        }
+
+        let status = if self.speed() == Some(1.0) && self.instructions_per_frame == self.config.instructions_per_frame {
+            None
+        }
+        else {
+            Some(self.speed_text())
+        };
+        let stats = [
+            format!("Quirks: {}", self.config.quirks.description()),
+            format!("DT: {:3}  ST: {:3}", self.cpu.delay_timer(), self.cpu.sound_timer()),
+        ];
+        self.osd.draw(frame, status.as_deref(), &stats);
+    }
+
+    /// Saves the screen, upscaled and filtered but without the overlays, into the first free numbered BMP file
+    fn save_screenshot(&mut self) {
+        let path = (1..)
+            .map(|i| format!("{}{}.bmp", SCREENSHOT_PREFIX, i))
+            .find(|path| !Path::new(path).exists())
+            .unwrap_or_default();
+        let frame = self.config.scaler.scale(&self.screen.pixels, WIDTH, HEIGHT);
+        let frame = self.config.filters.apply(frame, self.config.window_width, self.config.window_height);
+        match frame.save_bmp(&path) {
+            Ok(()) => self.notify(format!("Screenshot saved into {}", path)),
+            Err(e) => log_warning(format!("Unable to save the screenshot {}: {}", path, e)),
+        }
+    }
+
+    /// Starts recording the sound at the current emulated time.
+    /// The first recording goes to the configured file, the next ones to numbered files next to it
+    fn start_recording(&mut self) {
+        let base = self.config.record_audio.as_deref().unwrap_or(DEFAULT_RECORDING_FILE);
+        self.recordings += 1;
+        let path = if self.recordings == 1 {
+            base.to_string()
+        }
+        else {
+            let path = Path::new(base);
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("yac8e");
+            let name = format!("{}_{}.wav", stem, self.recordings);
+            path.with_file_name(name).to_string_lossy().into_owned()
+        };
+
+        match WavRecorder::new(&path, self.config.tone.clone(), self.volume, self.muted, self.emulated_time) {
+            Ok(mut recorder) => {
+                // The beep may already be playing
+                if self.beeping {
+                    recorder.play_beep(self.emulated_time);
+                }
+                self.recorder = Some(recorder);
+                self.notify(format!("Recording the sound into {}", path));
+            },
+            Err(e) => log_warning(format!("Unable to record the sound into {}: {}", path, e)),
+        }
+    }
+
+    /// Stops the recording, if any, at the current emulated time
+    fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            let path = recorder.path().to_string();
+            match recorder.finish(self.emulated_time) {
+                Ok(()) => self.notify(format!("Sound recorded into {}", path)),
+                Err(e) => log_warning(format!("Unable to write the recording {}: {}", path, e)),
+            }
+        }
+    }
+
+    /// Writes the recorded sound up to the current emulated time, stops the recording on error
+    /// or when it reaches the size limit of the WAV format
+    fn advance_recording(&mut self) {
+        let full = match &mut self.recorder {
+            Some(recorder) => match recorder.advance(self.emulated_time) {
+                Ok(()) => recorder.is_full(),
+                Err(e) => {
+                    log_warning(format!("Unable to write the recording {}: {}", recorder.path(), e));
+                    self.recorder = None;
+                    false
+                },
+            },
+            None => false,
+        };
+        if full {
+            self.notify("The recording reached the 4 GB limit of the WAV format");
+            self.stop_recording();
+        }
+    }
+
+    /// Returns an error if the ROM data doesn't fit in the RAM available for programs
+    fn check_rom(data: &[u8]) -> Result<(), Chip8Error> {
+        let max = MEMORY_SIZE - PROGRAM_START as usize;
+        if data.len() > max {
+            return Err(Chip8Error::RomTooLarge { size: data.len(), max: max });
+        }
+        Ok(())
+    }
 
-            // Here we execute one instruction, then we update the window display, then we sleep if required (happens in the display.update() func)
-            // Maybe it's better to separate these 2 steps into 2 separate timelines (i.e: Running more CPU instructions, then updating display)
-            chip8.run_instruction()?;
-            // Updates the screen and sleeps if necessary
-            chip8.handler.display.update(&chip8.screen.pixels)?;
+    /// Loads the ROM data into RAM, and keeps it to reload it on reset
+    pub fn load_rom(&mut self, data: &[u8]) -> Result<(), Chip8Error> {
+        Chip8::check_rom(data)?;
+        if self.config.random_ram {
+            let mut rng = cpu::new_rng(self.config.seed);
+            self.ram.fill_random(&mut rng);
+        }
+        self.ram.write_bytes(PROGRAM_START as usize, data)?;
+        if self.config.strict != StrictMode::OFF {
+            self.cpu.set_sanitizer(Sanitizer::new(data.len()));
         }
+        self.rom_data = data.to_vec();
 
         Ok(())
     }
 
-    /// Loads the ROM data into RAM
-    pub fn load_rom(&mut self, data: &Vec<u8>) -> Result<(), Box<dyn Error>> {
-        self.ram.write_bytes(PROGRAM_START as usize, &data[..])?;
+    /// Starts or stops the beep if the sound timer state changed, at the current emulated time
+    fn update_beep(&mut self) {
+        let active = self.cpu.is_sound_active();
+        if active != self.beeping {
+            self.set_beep(active);
+        }
+    }
+
+    /// Stops the beep while the emulation doesn't run, `update_beep` restarts it when it runs again
+    fn silence_beep(&mut self) {
+        if self.beeping {
+            self.set_beep(false);
+        }
+    }
+
+    /// Starts (`active` = `true`) or stops the beep at the current emulated time
+    fn set_beep(&mut self, active: bool) {
+        if active {
+            self.handler.sound.play_beep(self.emulated_time);
+        }
+        else {
+            self.handler.sound.stop_beep(self.emulated_time);
+        }
+        // The recorder gets the same edges as the sound component
+        if let Some(recorder) = &mut self.recorder {
+            if active {
+                recorder.play_beep(self.emulated_time);
+            }
+            else {
+                recorder.stop_beep(self.emulated_time);
+            }
+        }
+        self.beeping = active;
+    }
 
+    /// Runs `frames` frames, then as many frames as possible until the next frame must be presented if uncapped
+    fn run_frames(&mut self, frames: u32) -> Result<(), Chip8Error> {
+        for _ in 0..frames {
+            self.run_frame()?;
+        }
+        // Uncapped fast-forward runs as many frames as possible until the next frame must be presented
+        while self.frame_timer.is_uncapped() && self.frame_timer.has_time_left() {
+            self.run_frame()?;
+        }
         Ok(())
     }
 
+    /// Pauses the emulation if `result` is a strict mode violation, so the ROM can be inspected.
+    /// The other errors stop the emulator
+    fn halt_on_violation(&mut self, result: Result<(), Chip8Error>) -> Result<(), Box<dyn Error>> {
+        match result {
+            Err(Chip8Error::StrictViolation { .. }) => {
+                self.paused = true;
+                self.silence_beep();
+                self.notify("Halted by the strict mode");
+                self.update_title()?;
+                Ok(())
+            },
+            result => Ok(result?),
+        }
+    }
+
+    /// Runs a frame: the instructions of the frame, spread over its emulated duration, then the timers update.
+    /// Timers tick in emulated time rather than wall-clock time, which keeps the sound timer edges
+    /// aligned with the instructions, so the beeps get their exact length
+    ///
+    /// If the frame was started by stepping single instructions, only its remaining instructions are run
+    pub fn run_frame(&mut self) -> Result<(), Chip8Error> {
+        loop {
+            self.step_instruction()?;
+            if self.frame_instructions == 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Executes the next instruction of the current frame, then ends the frame after its last instruction
+    pub fn step_instruction(&mut self) -> Result<(), Chip8Error> {
+        if self.frame_instructions == 0 {
+            self.keypad.apply_events();
+            self.input_layer.apply(&mut self.keypad);
+        }
+        self.run_instruction()?;
+        self.frame_instructions += 1;
+
+        if self.frame_instructions >= self.instructions_per_frame {
+            self.frame_instructions = 0;
+            self.frames += 1;
+            self.emulated_time = FRAME_DURATION * self.frames;
+            self.cpu.update_timers(self.config.debug);
+            self.keypad.update_tested();
+            self.update_beep();
+            self.advance_recording();
+        }
+
+        // The instruction has run and is counted, so the emulation can resume after a halt
+        let reports = self.cpu.take_reports();
+        for report in reports.iter() {
+            log_warning(format!("Strict mode: {}", report));
+            self.osd.notify(report.to_string());
+            self.redraw = true;
+        }
+        match reports.into_iter().next() {
+            Some(report) if self.config.strict == StrictMode::HALT => Err(report),
+            _ => Ok(()),
+        }
+    }
+
     /// Executes the instruction pointed by the PC
-    pub fn run_instruction(&mut self) -> Result<(), Box<dyn Error>> {
-        self.cpu.run_instruction(&mut self.ram, &mut self.screen, &self.keypad, self.config.debug)?;
+    pub fn run_instruction(&mut self) -> Result<(), Chip8Error> {
+        self.cpu.run_instruction(&mut self.ram, &mut self.screen, &mut self.keypad, self.config.debug)?;
+        // The instruction may have set the sound timer
+        self.update_beep();
+        self.emulated_time += FRAME_DURATION / self.instructions_per_frame;
+        self.instructions += 1;
 
         if self.config.debug {
             log_debug(
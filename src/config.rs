@@ -0,0 +1,152 @@
+use crate::chip8::FRAME_RATE;
+use crate::filters::FilterPipeline;
+use crate::quirks::Quirks;
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+/// Default path of the config file, used if no other path is given on the command line
+pub const DEFAULT_CONFIG_FILE: &str = "yac8e.ini";
+/// Settings following the ROM, resolved again from the config file when another ROM is opened
+pub const ROM_SETTINGS: [&str; 4] = ["hertz", "ipf", "quirks", "filters"];
+
+#[derive(Debug, Default)]
+/// Settings read from the config file
+///
+/// The config file is a simple INI file. Settings at the top of the file apply to every ROM,
+/// settings under a `[ROM]` section only apply to the ROM with that file name (e.g. `[PONG]` or `[pong.ch8]`)
+/// and override the global ones. Setting names are the same as the long command line options:
+///
+/// ```ini
+/// scaler = scale2x
+///
+/// [PONG]
+/// filters = scanlines:0.4, vignette
+/// ```
+pub struct ConfigFile {
+    /// Settings applying to the current ROM
+    values: HashMap<String, String>,
+}
+
+impl ConfigFile {
+    /// Loads the settings of `path` applying to the ROM `rom`
+    pub fn load(path: &Path, rom: &str) -> Result<Self, Box<dyn Error>> {
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Can't read config file {}: {}", path.display(), e))?;
+
+        let rom_path = Path::new(rom);
+        let rom_names: Vec<String> = rom_path.file_name().iter()
+            .chain(rom_path.file_stem().iter())
+            .map(|n| n.to_string_lossy().to_lowercase())
+            .collect();
+
+        let mut global = HashMap::new();
+        let mut specific = HashMap::new();
+        // `None` while we are not in any section yet, `Some(true)` if we are in the current ROM section
+        let mut in_rom_section: Option<bool> = None;
+
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+
+            if line.starts_with('[') && line.ends_with(']') {
+                let section = line[1..line.len()-1].trim().to_lowercase();
+                in_rom_section = Some(rom_names.contains(&section));
+                continue;
+            }
+
+            let mut parts = line.splitn(2, '=');
+            let (key, value) = match (parts.next(), parts.next()) {
+                (Some(k), Some(v)) => (k.trim().to_string(), v.trim().to_string()),
+                _ => {
+                    return Err(format!("Invalid line {} in config file {}: {}", i + 1, path.display(), line).into());
+                }
+            };
+
+            match in_rom_section {
+                None => { global.insert(key, value); },
+                Some(true) => { specific.insert(key, value); },
+                Some(false) => {},
+            }
+        }
+
+        global.extend(specific);
+
+        Ok(ConfigFile {
+            values: global,
+        })
+    }
+
+    /// Returns the value of the setting `key`, `None` if it isn't set
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.values.get(key).map(|v| v.as_str())
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Values of the settings following the ROM, resolved from the command line and the section of the ROM
+pub struct RomSettings {
+    /// Behaviors differing between interpreters
+    pub quirks: Quirks,
+    /// Number of instructions run in each 60 Hz frame
+    pub instructions_per_frame: u32,
+    /// Post-processing filters applied to the upscaled screen
+    pub filters: FilterPipeline,
+}
+
+#[derive(Clone, Debug, Default)]
+/// Command line values of the settings following the ROM (`ROM_SETTINGS`), kept to resolve them again
+/// with the config file section of each ROM opened
+pub struct RomArguments {
+    /// Values given on the command line, which override the config file
+    given: HashMap<String, String>,
+    /// Default values, used if neither the command line nor the config file set the setting
+    defaults: HashMap<String, String>,
+}
+
+impl RomArguments {
+    /// Sets the command line `value` of the setting `name`, `given` if it was on the command line rather than a default
+    pub fn insert(&mut self, name: &str, value: Option<&str>, given: bool) {
+        if let Some(value) = value {
+            let values = if given { &mut self.given } else { &mut self.defaults };
+            values.insert(name.to_string(), value.to_string());
+        }
+    }
+
+    /// Returns the value of the setting `name`: the command line overrides `config`, which overrides the default value
+    fn get<'a>(&'a self, config: &'a ConfigFile, name: &str) -> Option<&'a str> {
+        self.given.get(name).map(|v| v.as_str())
+            .or_else(|| config.get(name))
+            .or_else(|| self.defaults.get(name).map(|v| v.as_str()))
+    }
+
+    /// Resolves the settings following the ROM, `config` holding the settings of the ROM
+    pub fn resolve(&self, config: &ConfigFile) -> Result<RomSettings, Box<dyn Error>> {
+        // `ipf` overrides `hertz`, e.g. 500 Hz gives 8 instructions per frame
+        let instructions_per_frame = match self.get(config, "ipf") {
+            Some(v) => match v.parse::<u32>() {
+                Ok(x) if (1..=100000).contains(&x) => x,
+                _ => return Err("Invalid instructions per frame value".into()),
+            },
+            None => match self.get(config, "hertz").map(|v| v.parse::<f64>()) {
+                Some(Ok(x)) if x > 0.0 && x < 100000.0 => ((x / FRAME_RATE).round() as u32).max(1),
+                _ => return Err("Invalid Hertz value".into()),
+            },
+        };
+
+        let quirks = Quirks::parse(self.get(config, "quirks").unwrap_or(""))
+            .map_err(|e| format!("Invalid quirks value: {}", e))?;
+        let filters = FilterPipeline::parse(self.get(config, "filters").unwrap_or(""))
+            .map_err(|e| format!("Invalid filters value: {}", e))?;
+
+        Ok(RomSettings {
+            quirks: quirks,
+            instructions_per_frame: instructions_per_frame,
+            filters: filters,
+        })
+    }
+}
@@ -1,17 +1,23 @@
 use crate::ram::Ram;
 use crate::screen::Screen;
 use crate::keypad::Keypad;
-use crate::utils::{log_debug, log_warning, register_error, stack_pop_error};
+use crate::quirks::Quirks;
+use crate::sanitizer::Sanitizer;
+use crate::error::Chip8Error;
+use crate::utils::{log_debug, log_warning};
 
-use std::error::Error;
 use std::time::Instant;
 
-use rand::Rng;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
 
 /// Address pointing to the start of the program (executable instructions) in a CHIP-8 ROM
 pub const PROGRAM_START: u16 = 0x200;
+/// Maximum number of return addresses on the stack. It is beyond the 16 levels of the COSMAC VIP,
+/// so the strict mode can report the deeper calls, but bounds the memory of a ROM recursing forever
+pub const STACK_LIMIT: usize = 256;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 /// Struct emulating the CPU
 pub struct Cpu {
     /// Vector containing the 16 8-bit registers, referred as V0 to VF
@@ -20,20 +26,37 @@ pub struct Cpu {
     pc: u16,
     /// The 16-bit register used to store memory addresses,
     i: u16,
-    /// Vector emulating the stack containing 16 16-bit values at maximum
+    /// Vector emulating the stack containing `STACK_LIMIT` 16-bit values at maximum
     /// Only used for return addresses in CHIP-8
     stack: Vec<u16>,
     /// Delay timer
     delay_timer: u8,
     /// Sound timer
     sound_timer: u8,
+    /// Behaviors differing between interpreters
+    quirks: Quirks,
+    /// Random generator of `CXNN`, part of the state so a seeded run can be replayed
+    rng: ChaCha8Rng,
+    /// Checks of the strict mode, `None` when it is off
+    sanitizer: Option<Sanitizer>,
     debug_time: Instant,
     debug_count: u64,
 }
 
+/// Creates the random generator of `CXNN`, seeded with `seed` if set.
+/// ChaCha8 gives the same numbers on every platform and with every version of `rand`, unlike `StdRng`,
+/// so a seeded run can be replayed with another build
+pub fn new_rng(seed: Option<u64>) -> ChaCha8Rng {
+    match seed {
+        Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+        None => ChaCha8Rng::from_entropy(),
+    }
+}
+
 impl Cpu {
-    /// Creates a new `Cpu` object
-    pub fn new() -> Self {
+    /// Creates a new `Cpu` object with the given `Quirks`, and `rng` as the random generator of `CXNN`.
+    /// A seeded generator (see `new_rng`) gives the same run for the same ROM and inputs
+    pub fn new(quirks: Quirks, rng: ChaCha8Rng) -> Self {
         Cpu {
             vx: vec![0; 16],
             pc: PROGRAM_START,
@@ -41,22 +64,59 @@ impl Cpu {
             stack: vec![],
             delay_timer: 0,
             sound_timer: 0,
+            quirks: quirks,
+            rng: rng,
+            sanitizer: None,
             debug_time: Instant::now(),
             debug_count: 0,
         }
     }
 
-    /// Runs a single instruction at `pc` address
-    pub fn run_instruction(&mut self, ram: &mut Ram, screen: &mut Screen, keypad: &Keypad, debug: bool) -> Result<(), Box<dyn Error>> {
+    /// Runs a single instruction at `pc` address.
+    /// The returned errors have the address and the opcode of the instruction set
+    pub fn run_instruction(&mut self, ram: &mut Ram, screen: &mut Screen, keypad: &mut Keypad, debug: bool) -> Result<(), Chip8Error> {
+        let pc = self.pc;
         // Big-endian address
-        let high = ram.read_byte(self.pc as usize)? as u16;
-        let low = ram.read_byte((self.pc + 1) as usize)? as u16;
+        let high = ram.read_byte(pc as usize).map_err(|e| e.at(pc, None))? as u16;
+        let low = ram.read_byte(pc as usize + 1).map_err(|e| e.at(pc, None))? as u16;
         let instruction: u16 = (high << 8) | low;
 
+        self.sanitize(|s| s.start(pc, instruction));
+        let i = self.i;
+        self.execute(instruction, ram, screen, keypad, debug).map_err(|e| e.at(pc, Some(instruction)))?;
+        if self.i != i {
+            let i = self.i;
+            self.sanitize(|s| s.index(i));
+        }
+        Ok(())
+    }
+
+    /// Turns on the checks of the strict mode with `sanitizer`
+    pub fn set_sanitizer(&mut self, sanitizer: Sanitizer) {
+        self.sanitizer = Some(sanitizer);
+    }
+
+    /// Returns the strict mode violations found since the last call
+    pub fn take_reports(&mut self) -> Vec<Chip8Error> {
+        match &mut self.sanitizer {
+            Some(sanitizer) => sanitizer.take_reports(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Runs the check `f` if the strict mode is on
+    fn sanitize<F: FnOnce(&mut Sanitizer)>(&mut self, f: F) {
+        if let Some(sanitizer) = &mut self.sanitizer {
+            f(sanitizer);
+        }
+    }
+
+    /// Executes `instruction`, the instruction at `pc` address
+    fn execute(&mut self, instruction: u16, ram: &mut Ram, screen: &mut Screen, keypad: &mut Keypad, debug: bool) -> Result<(), Chip8Error> {
         if debug {
             log_debug(
                 format!(
-                    "Instruction: {:#X?}, high: {:#X?}, low: {:#X?}", instruction, high, low
+                    "Instruction: {:#X?}", instruction
                 )
             );
         }
@@ -87,11 +147,11 @@ impl Cpu {
                     0xEE => {
                         // return;
                         self.pc = self.stack.pop().ok_or(
-                            stack_pop_error()
+                            Chip8Error::StackUnderflow { pc: self.pc, opcode: instruction }
                         )?;
                     },
                     _ => {
-                        return Err(format!("Unrecognized opcode: {:#X}", instruction).into());
+                        return Err(Chip8Error::UnknownOpcode { pc: self.pc, opcode: instruction });
                     },
                 }
             },
@@ -102,7 +162,12 @@ impl Cpu {
             0x2 => {
                 // Call subroutine at address NNN
                 // *(0xNNN)()
+                if self.stack.len() >= STACK_LIMIT {
+                    return Err(Chip8Error::StackOverflow { pc: self.pc, opcode: instruction });
+                }
                 self.stack.push(self.pc + 2);
+                let depth = self.stack.len();
+                self.sanitize(|s| s.call(depth));
                 self.pc = nnn;
             },
             0x3 => {
@@ -179,7 +244,7 @@ impl Cpu {
                         else {
                             self.write_reg_vx(0xF, 0);
                         }
-                        self.write_reg_vx(x, vx - vy);
+                        self.write_reg_vx(x, vx.wrapping_sub(vy));
                     },
                     0x6 => {
                         // Vx >>= 1
@@ -194,7 +259,7 @@ impl Cpu {
                         else {
                             self.write_reg_vx(0xF, 0);
                         }
-                        self.write_reg_vx(x, vy - vx);
+                        self.write_reg_vx(x, vy.wrapping_sub(vx));
                     },
                     0xE => {
                         // Vx <<= 1
@@ -202,7 +267,7 @@ impl Cpu {
                         self.write_reg_vx(x, vx << 1);
                     },
                     _ => {
-                        return Err(format!("Unrecognized opcode: {:#X}", instruction).into());
+                        return Err(Chip8Error::UnknownOpcode { pc: self.pc, opcode: instruction });
                     }
                 }
                 self.pc += 2;
@@ -220,12 +285,11 @@ impl Cpu {
             },
             0xB => {
                 // PC = V0 + NNN
-                self.pc = self.read_reg_vx(0) as u16 + nnn;
+                self.pc = nnn.wrapping_add(self.read_reg_vx(0) as u16);
             },
             0xC => {
                 // Vx = rand() & NN
-                let mut rng = rand::thread_rng();
-                let num: u8 = rng.gen_range(0, 255);
+                let num: u8 = self.rng.gen_range(0, 255);
                 self.write_reg_vx(x, num & n);
                 self.pc += 2;
             },
@@ -241,15 +305,15 @@ impl Cpu {
                     0x9E => {
                         // if (key() == Vx)
                         let key = self.read_reg_vx(x);
-                        self.skip_if(*keypad.is_key_pressed(key)?);
+                        self.skip_if(keypad.test_key(key)?);
                     },
                     0xA1 => {
                         // 	if (key() != Vx)
                         let key = self.read_reg_vx(x);
-                        self.skip_if(!(*keypad.is_key_pressed(key)?));
+                        self.skip_if(!keypad.test_key(key)?);
                     },
                     _ => {
-                        return Err(format!("Unrecognized opcode: {:#X}", instruction).into());
+                        return Err(Chip8Error::UnknownOpcode { pc: self.pc, opcode: instruction });
                     },
                 }
             }
@@ -262,9 +326,19 @@ impl Cpu {
                     },
                     0x0A => {
                         // Vx = get_key()
-                        if let Some(key) = keypad.first_pressed_key() {
-                            self.write_reg_vx(x, key);
-                            self.pc += 2;
+                        let key = if self.quirks.key_wait_press {
+                            keypad.first_pressed_key()
+                        }
+                        else {
+                            keypad.wait_key_release()
+                        };
+                        match key {
+                            Some(key) => {
+                                self.write_reg_vx(x, key);
+                                self.pc += 2;
+                                keypad.set_waiting(false);
+                            },
+                            None => keypad.set_waiting(true),
                         }
                     },
                     0x15 => {
@@ -280,7 +354,7 @@ impl Cpu {
                     0x1E => {
                         // I += Vx
                         let vx = self.read_reg_vx(x);
-                        self.i += vx as u16;
+                        self.i = self.i.wrapping_add(vx as u16);
                         self.pc += 2;
                     },
                     0x29 => {
@@ -293,9 +367,11 @@ impl Cpu {
                         // *(I+1) = BCD(2);
                         // *(I+2) = BCD(1);
                         let vx = self.read_reg_vx(x);
+                        let i = self.i as usize;
+                        self.sanitize(|s| s.write(i, 3));
                         ram.write_byte(self.i as usize, vx / 100)?;
-                        ram.write_byte((self.i + 1) as usize, (vx % 100) / 10)?;
-                        ram.write_byte((self.i + 2) as usize, vx % 10)?;
+                        ram.write_byte(self.i as usize + 1, (vx % 100) / 10)?;
+                        ram.write_byte(self.i as usize + 2, vx % 10)?;
                         self.pc += 2;
                     },
                     0x55 => {
@@ -305,13 +381,15 @@ impl Cpu {
                         // I += x+1
 
                         let index = (x+1) as usize;
+                        let i = self.i as usize;
+                        self.sanitize(|s| s.write(i, index));
                         ram.write_bytes(
                             self.i as usize,
                             self.vx.get(0..index).ok_or(
-                                register_error(index)
+                                Chip8Error::InvalidRegister { pc: self.pc, opcode: instruction, register: x as usize }
                             )?
                         )?;
-                        self.i += x as u16 + 1;
+                        self.i = self.i.wrapping_add(x as u16 + 1);
 
                         // TODO
                         self.pc += 2;
@@ -324,24 +402,26 @@ impl Cpu {
                         // I += x+1
 
                         let index = (x+1) as usize;
+                        let i = self.i as usize;
+                        self.sanitize(|s| s.read(i, index));
                         self.vx
                             .get_mut(0..index).ok_or(
-                                register_error(index)
+                                Chip8Error::InvalidRegister { pc: self.pc, opcode: instruction, register: x as usize }
                             )?
                             .copy_from_slice(
                                 ram.read_bytes(self.i as usize, index)?
                             );
-                        self.i += x as u16 + 1;
+                        self.i = self.i.wrapping_add(x as u16 + 1);
 
                         self.pc += 2;
                     },
                     _ => {
-                        return Err(format!("Unrecognized opcode: {:#X}", instruction).into());
+                        return Err(Chip8Error::UnknownOpcode { pc: self.pc, opcode: instruction });
                     },
                 }
             }
             _ => {
-                return Err(format!("Unrecognized opcode: {:#X}", instruction).into());
+                return Err(Chip8Error::UnknownOpcode { pc: self.pc, opcode: instruction });
             },
         }
 
@@ -371,7 +451,7 @@ impl Cpu {
     /// Draws a sprite to the screen
     /// CHIP-8 sprites are always eight pixels wide and between one to fifteen pixels high
     /// One byte corresponds to one row of a given sprite
-    pub fn draw_sprite(&mut self, ram: &mut Ram, screen: &mut Screen, debug: bool, x: u8, y: u8, n: u8) -> Result<(), Box<dyn Error>> {
+    pub fn draw_sprite(&mut self, ram: &mut Ram, screen: &mut Screen, debug: bool, x: u8, y: u8, n: u8) -> Result<(), Chip8Error> {
         if debug {
             log_debug(
                 format!(
@@ -384,10 +464,14 @@ impl Cpu {
             );
         }
 
+        let i = self.i as usize;
+        self.sanitize(|s| s.read(i, n as usize));
+
         let mut should_set_vf = false;
         for sprite_y in 0..n {
-            let byte = ram.read_byte((self.i + sprite_y as u16) as usize)?;
-            if screen.draw_byte(x, y + sprite_y, byte)? {
+            let byte = ram.read_byte(self.i as usize + sprite_y as usize)?;
+            // The screen wraps the coordinates, 256 being a multiple of its height
+            if screen.draw_byte(x, y.wrapping_add(sprite_y), byte)? {
                 should_set_vf = true;
             }
         }
@@ -402,6 +486,26 @@ impl Cpu {
         Ok(())
     }
 
+    /// Returns the address of the next instruction
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    /// Returns the value of the delay timer
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    /// Returns the value of the sound timer
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// Returns whether the sound timer is active or not
+    pub fn is_sound_active(&self) -> bool {
+        self.sound_timer > 0
+    }
+
     /// Updates the timers (delay_timer / sound_timer)
     ///
     /// Returns whether the sound timer is active or not
@@ -431,3 +535,75 @@ impl Cpu {
         self.sound_timer > 0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ram::MEMORY_SIZE;
+
+    /// Draws random digits at random positions forever:
+    /// V0, V1, V2 = random, I = 0x300 + V3, BCD of V0 at I, draw it at (V1, V2), delay = V0, V3 += 1
+    const RANDOM_ROM: [u8; 18] = [
+        0xC0, 0xFF, 0xC1, 0x3F, 0xC2, 0x1F, 0xA3, 0x00, 0xF3, 0x1E,
+        0xF0, 0x33, 0xD1, 0x25, 0xF0, 0x15, 0x73, 0x01,
+    ];
+    /// Instructions run in each frame
+    const INSTRUCTIONS_PER_FRAME: u32 = 10;
+
+    /// Runs `RANDOM_ROM` (looping back to its start) for `frames` frames, `seed` seeding `CXNN`
+    fn run(seed: u64, frames: u32) -> (Cpu, Ram, Screen) {
+        let mut cpu = Cpu::new(Quirks::default(), new_rng(Some(seed)));
+        let mut ram = Ram::new();
+        let mut screen = Screen::new();
+        let mut keypad = Keypad::new();
+        ram.write_bytes(PROGRAM_START as usize, &RANDOM_ROM).unwrap();
+        ram.write_bytes(PROGRAM_START as usize + RANDOM_ROM.len(), &[0x12, 0x00]).unwrap();
+
+        for _ in 0..frames {
+            for _ in 0..INSTRUCTIONS_PER_FRAME {
+                cpu.run_instruction(&mut ram, &mut screen, &mut keypad, false).unwrap();
+            }
+            cpu.update_timers(false);
+        }
+        (cpu, ram, screen)
+    }
+
+    /// State of the registers, the stack and the timers of `cpu`
+    fn registers(cpu: &Cpu) -> (Vec<u8>, u16, u16, Vec<u16>, u8, u8) {
+        (cpu.vx.clone(), cpu.pc, cpu.i, cpu.stack.clone(), cpu.delay_timer, cpu.sound_timer)
+    }
+
+    #[test]
+    fn same_seed_gives_the_same_run() {
+        let (cpu1, ram1, screen1) = run(1234, 600);
+        let (cpu2, ram2, screen2) = run(1234, 600);
+
+        assert_eq!(registers(&cpu1), registers(&cpu2));
+        assert_eq!(ram1.read_bytes(0, MEMORY_SIZE).unwrap(), ram2.read_bytes(0, MEMORY_SIZE).unwrap());
+        assert_eq!(screen1.pixels, screen2.pixels);
+    }
+
+    /// Returns the first `count` numbers of `CXNN` with the random generator seeded with `seed`
+    fn random_numbers(seed: u64, count: usize) -> Vec<u8> {
+        let mut cpu = Cpu::new(Quirks::default(), new_rng(Some(seed)));
+        let mut ram = Ram::new();
+        ram.write_bytes(PROGRAM_START as usize, &[0xC0, 0xFF]).unwrap();
+
+        (0..count).map(|_| {
+            cpu.pc = PROGRAM_START;
+            cpu.run_instruction(&mut ram, &mut Screen::new(), &mut Keypad::new(), false).unwrap();
+            cpu.read_reg_vx(0)
+        }).collect()
+    }
+
+    #[test]
+    fn other_seed_gives_other_run() {
+        assert_eq!(random_numbers(1, 16), random_numbers(1, 16));
+        assert_ne!(random_numbers(1, 16), random_numbers(2, 16));
+
+        let (_, ram1, screen1) = run(1, 600);
+        let (_, ram2, screen2) = run(2, 600);
+        assert_ne!(ram1.read_bytes(0, MEMORY_SIZE).unwrap(), ram2.read_bytes(0, MEMORY_SIZE).unwrap());
+        assert_ne!(screen1.pixels, screen2.pixels);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::sanitizer::Violation;
+
+use std::error::Error;
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+/// Errors of the CHIP-8 VM, with the state of the machine when they happened
+///
+/// `pc` is the address of the faulty instruction and `opcode` the instruction itself.
+/// The RAM and the keypad don't know them, they are set by the CPU, and are `None` for errors raised
+/// outside of an instruction (or `opcode` only, if the instruction couldn't be fetched)
+pub enum Chip8Error {
+    /// The instruction isn't a CHIP-8 instruction
+    UnknownOpcode { pc: u16, opcode: u16 },
+    /// `00EE` returned from a subroutine while the stack is empty
+    StackUnderflow { pc: u16, opcode: u16 },
+    /// `2NNN` called a subroutine while the stack is full
+    StackOverflow { pc: u16, opcode: u16 },
+    /// `size` bytes were read at `address`, outside of the RAM
+    OutOfBoundsRead { pc: Option<u16>, opcode: Option<u16>, address: usize, size: usize },
+    /// `size` bytes were written at `address`, outside of the RAM
+    OutOfBoundsWrite { pc: Option<u16>, opcode: Option<u16>, address: usize, size: usize },
+    /// The instruction used the register V`register`, which doesn't exist
+    InvalidRegister { pc: u16, opcode: u16, register: usize },
+    /// The instruction tested the key `key`, which doesn't exist
+    InvalidKey { pc: Option<u16>, opcode: Option<u16>, key: u8 },
+    /// The ROM of `size` bytes doesn't fit in the `max` bytes of RAM available for programs
+    RomTooLarge { size: usize, max: usize },
+    /// The instruction did something real hardware wouldn't tolerate, found by the strict mode
+    StrictViolation { pc: u16, opcode: u16, violation: Violation },
+}
+
+impl Chip8Error {
+    /// Sets the address `pc` and the `opcode` of the instruction which raised the error, if they aren't set.
+    /// `opcode` is `None` if the instruction couldn't be fetched
+    pub fn at(self, pc: u16, opcode: Option<u16>) -> Self {
+        match self {
+            Chip8Error::OutOfBoundsRead { pc: None, address, size, .. } => {
+                Chip8Error::OutOfBoundsRead { pc: Some(pc), opcode: opcode, address: address, size: size }
+            },
+            Chip8Error::OutOfBoundsWrite { pc: None, address, size, .. } => {
+                Chip8Error::OutOfBoundsWrite { pc: Some(pc), opcode: opcode, address: address, size: size }
+            },
+            Chip8Error::InvalidKey { pc: None, key, .. } => {
+                Chip8Error::InvalidKey { pc: Some(pc), opcode: opcode, key: key }
+            },
+            error => error,
+        }
+    }
+}
+
+/// Describes the instruction which raised an error, e.g. `: F065 at 0x206`, empty if it isn't known
+fn instruction(pc: Option<u16>, opcode: Option<u16>) -> String {
+    match (pc, opcode) {
+        (Some(pc), Some(opcode)) => format!(": {:04X} at {:#05X}", opcode, pc),
+        (Some(pc), None) => format!(" at {:#05X}", pc),
+        _ => String::new(),
+    }
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Chip8Error::UnknownOpcode { pc, opcode } => {
+                write!(f, "Unknown opcode{}", instruction(Some(pc), Some(opcode)))
+            },
+            Chip8Error::StackUnderflow { pc, opcode } => {
+                write!(f, "Return with an empty stack{}", instruction(Some(pc), Some(opcode)))
+            },
+            Chip8Error::StackOverflow { pc, opcode } => {
+                write!(f, "Call with a full stack{}", instruction(Some(pc), Some(opcode)))
+            },
+            Chip8Error::OutOfBoundsRead { pc, opcode, address, size } => {
+                write!(f, "Out of bounds read of {} bytes at address {:#X}{}", size, address, instruction(pc, opcode))
+            },
+            Chip8Error::OutOfBoundsWrite { pc, opcode, address, size } => {
+                write!(f, "Out of bounds write of {} bytes at address {:#X}{}", size, address, instruction(pc, opcode))
+            },
+            Chip8Error::InvalidRegister { pc, opcode, register } => {
+                write!(f, "Non existing register V{}{}", register, instruction(Some(pc), Some(opcode)))
+            },
+            Chip8Error::InvalidKey { pc, opcode, key } => {
+                write!(f, "Non existing key {:#X}{}", key, instruction(pc, opcode))
+            },
+            Chip8Error::RomTooLarge { size, max } => {
+                write!(f, "ROM too large: {} bytes, {} bytes at most", size, max)
+            },
+            Chip8Error::StrictViolation { pc, opcode, ref violation } => {
+                write!(f, "{}{}", violation, instruction(Some(pc), Some(opcode)))
+            },
+        }
+    }
+}
+
+impl Error for Chip8Error {}
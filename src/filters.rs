@@ -0,0 +1,262 @@
+use crate::screen::WIDTH;
+use crate::frame::Frame;
+
+use std::error::Error;
+
+/// Smallest size of a CHIP-8 pixel drawn with scanlines or a grid, smaller pixels would be entirely darkened
+const MIN_CELL: usize = 2;
+
+#[derive(Clone, Debug)]
+/// Enum containing the different post-processing filters that are supported
+///
+/// The filters are applied on the CPU to the upscaled frame, so they work with every backend
+pub enum Filter {
+    /// Darkens the bottom of each CHIP-8 pixel row, `intensity` being how dark the lines are (0.0 - 1.0)
+    SCANLINES { intensity: f32 },
+    /// Draws a dark line around each CHIP-8 pixel, `intensity` being how dark the lines are (0.0 - 1.0)
+    GRID { intensity: f32 },
+    /// Darkens the corners of the screen, `strength` being how dark the corners are (0.0 - 1.0)
+    VIGNETTE { strength: f32 },
+    /// Approximation of a CRT screen: barrel distortion of `curvature` and glow around lit pixels of `bloom`
+    CRT { curvature: f32, bloom: f32 },
+}
+
+impl Filter {
+    /// Parses a filter from a string like `name[:param[:param]]`, e.g. `scanlines:0.5` or `crt:0.1:0.3`
+    ///
+    /// Missing parameters take their default value
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or("").trim().to_lowercase();
+        let params = parts
+            .map(|p| p.trim().parse::<f32>().map_err(|_| format!("Invalid filter parameter: {}", p)))
+            .collect::<Result<Vec<f32>, String>>()?;
+        let param = |i: usize, default: f32| params.get(i).copied().unwrap_or(default).max(0.0);
+
+        let filter = match name.as_str() {
+            "scanlines" => Filter::SCANLINES { intensity: param(0, 0.5).min(1.0) },
+            "grid" => Filter::GRID { intensity: param(0, 0.3).min(1.0) },
+            "vignette" => Filter::VIGNETTE { strength: param(0, 0.4).min(1.0) },
+            "crt" => Filter::CRT { curvature: param(0, 0.1), bloom: param(1, 0.3) },
+            _ => return Err(format!("Unknown filter: {}", name).into()),
+        };
+        Ok(filter)
+    }
+
+    /// Applies the filter to `frame`, `cell` being the size of a CHIP-8 pixel in the frame
+    fn apply(&self, frame: &mut Frame, cell: usize) {
+        match *self {
+            Filter::SCANLINES { intensity } => scanlines(frame, cell, intensity),
+            Filter::GRID { intensity } => grid(frame, cell, intensity),
+            Filter::VIGNETTE { strength } => vignette(frame, strength),
+            Filter::CRT { curvature, bloom } => {
+                crt_bloom(frame, cell, bloom);
+                crt_curvature(frame, curvature);
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+/// Chain of post-processing filters applied to the upscaled frame before it is displayed
+pub struct FilterPipeline {
+    /// Filters, applied in order
+    pub filters: Vec<Filter>,
+}
+
+impl FilterPipeline {
+    /// Parses a comma-separated list of filters, e.g. `scanlines:0.4,vignette`
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let filters = s.split(',')
+            .filter(|f| !f.trim().is_empty())
+            .map(Filter::parse)
+            .collect::<Result<Vec<Filter>, Box<dyn Error>>>()?;
+
+        Ok(FilterPipeline {
+            filters: filters,
+        })
+    }
+
+    /// Applies all the filters to `frame`
+    ///
+    /// The filters need more resolution than a 64x32 frame, so the frame is first enlarged with
+    /// the biggest integer factor fitting in the window, the backend doesn't need to stretch it anymore
+    pub fn apply(&self, frame: Frame, window_width: usize, window_height: usize) -> Frame {
+        if self.filters.is_empty() || frame.width == 0 || frame.height == 0 {
+            return frame;
+        }
+
+        let factor = (window_width / frame.width).min(window_height / frame.height).max(1);
+        let mut frame = enlarge(&frame, factor);
+        let cell = (frame.width / WIDTH).max(1);
+
+        for filter in self.filters.iter() {
+            filter.apply(&mut frame, cell);
+        }
+
+        frame
+    }
+}
+
+/// Enlarges the frame `factor` times, without any filtering
+fn enlarge(frame: &Frame, factor: usize) -> Frame {
+    let mut out = Frame::new(frame.width * factor, frame.height * factor);
+    for (y, row) in out.pixels.chunks_mut(frame.width * factor).enumerate() {
+        let source_row = &frame.pixels[(y / factor) * frame.width..];
+        for (x, p) in row.iter_mut().enumerate() {
+            *p = source_row[x / factor];
+        }
+    }
+    out
+}
+
+/// Multiplies each channel of `color` by `factor`
+pub fn shade(color: u32, factor: f32) -> u32 {
+    let mut result = 0;
+    for shift in [0, 8, 16].iter() {
+        let channel = ((color >> shift) & 0xFF) as f32 * factor;
+        result |= (channel.round().clamp(0.0, 255.0) as u32) << shift;
+    }
+    result
+}
+
+/// Darkens the bottom lines of each CHIP-8 pixel row, unless the pixels are smaller than `MIN_CELL`
+fn scanlines(frame: &mut Frame, cell: usize, intensity: f32) {
+    if cell < MIN_CELL {
+        return;
+    }
+    // The bottom third of each CHIP-8 pixel row is darkened, at least 1 line
+    let dark_lines = (cell / 3).max(1);
+    for (y, row) in frame.pixels.chunks_mut(frame.width).enumerate() {
+        if y % cell >= cell - dark_lines {
+            for p in row.iter_mut() {
+                *p = shade(*p, 1.0 - intensity);
+            }
+        }
+    }
+}
+
+/// Darkens the right and bottom borders of each CHIP-8 pixel, unless the pixels are smaller than `MIN_CELL`
+fn grid(frame: &mut Frame, cell: usize, intensity: f32) {
+    if cell < MIN_CELL {
+        return;
+    }
+    for (y, row) in frame.pixels.chunks_mut(frame.width).enumerate() {
+        for (x, p) in row.iter_mut().enumerate() {
+            if x % cell == cell - 1 || y % cell == cell - 1 {
+                *p = shade(*p, 1.0 - intensity);
+            }
+        }
+    }
+}
+
+/// Darkens the pixels proportionally to their squared distance to the center of the screen
+fn vignette(frame: &mut Frame, strength: f32) {
+    let width = frame.width as f32;
+    let height = frame.height as f32;
+    for (y, row) in frame.pixels.chunks_mut(frame.width).enumerate() {
+        let v = 2.0 * y as f32 / height - 1.0;
+        for (x, p) in row.iter_mut().enumerate() {
+            let u = 2.0 * x as f32 / width - 1.0;
+            // Squared distance to the center, 1.0 in the corners
+            let distance = (u * u + v * v) / 2.0;
+            *p = shade(*p, 1.0 - strength * distance);
+        }
+    }
+}
+
+/// Adds a glow around the lit pixels, by adding a blurred copy of the frame to itself
+fn crt_bloom(frame: &mut Frame, cell: usize, bloom: f32) {
+    if bloom <= 0.0 {
+        return;
+    }
+
+    let height = frame.height;
+    let width = frame.width;
+    let radius = cell.max(1);
+
+    // Separable box blur of each channel, using running sums
+    let mut glow = vec![[0f32; 3]; width * height];
+    for (y, row) in frame.pixels.chunks(width).enumerate() {
+        let mut sum = [0f32; 3];
+        for x in 0..(width + radius) {
+            if x < width {
+                add_channels(&mut sum, row[x], 1.0);
+            }
+            if x > 2 * radius {
+                add_channels(&mut sum, row[x - 2 * radius - 1], -1.0);
+            }
+            if x >= radius {
+                glow[y * width + x - radius] = sum;
+            }
+        }
+    }
+    let horizontal = glow.clone();
+    for x in 0..width {
+        let mut sum = [0f32; 3];
+        for y in 0..(height + radius) {
+            if y < height {
+                add_arrays(&mut sum, &horizontal[y * width + x], 1.0);
+            }
+            if y > 2 * radius {
+                add_arrays(&mut sum, &horizontal[(y - 2 * radius - 1) * width + x], -1.0);
+            }
+            if y >= radius {
+                glow[(y - radius) * width + x] = sum;
+            }
+        }
+    }
+
+    let area = ((2 * radius + 1) * (2 * radius + 1)) as f32;
+    for (p, g) in frame.pixels.iter_mut().zip(glow.iter()) {
+        let mut result = 0;
+        for (i, shift) in [0, 8, 16].iter().enumerate() {
+            let channel = ((*p >> shift) & 0xFF) as f32 + bloom * g[i] / area;
+            result |= (channel.round().min(255.0) as u32) << shift;
+        }
+        *p = result;
+    }
+}
+
+/// Adds each channel of `color` multiplied by `sign` to `sum`
+fn add_channels(sum: &mut [f32; 3], color: u32, sign: f32) {
+    for (i, shift) in [0, 8, 16].iter().enumerate() {
+        sum[i] += sign * ((color >> shift) & 0xFF) as f32;
+    }
+}
+
+/// Adds `values` multiplied by `sign` to `sum`
+fn add_arrays(sum: &mut [f32; 3], values: &[f32; 3], sign: f32) {
+    for i in 0..3 {
+        sum[i] += sign * values[i];
+    }
+}
+
+/// Barrel distortion, the borders of the screen are bent outwards like on a curved CRT screen
+fn crt_curvature(frame: &mut Frame, curvature: f32) {
+    if curvature <= 0.0 {
+        return;
+    }
+
+    let source = frame.clone();
+    let height = frame.height as f32;
+    let width = frame.width as f32;
+    for (y, row) in frame.pixels.chunks_mut(frame.width).enumerate() {
+        let v = 2.0 * (y as f32 + 0.5) / height - 1.0;
+        for (x, p) in row.iter_mut().enumerate() {
+            let u = 2.0 * (x as f32 + 0.5) / width - 1.0;
+            let distortion = 1.0 + curvature * (u * u + v * v);
+            let (su, sv) = (u * distortion, v * distortion);
+
+            *p = if su.abs() > 1.0 || sv.abs() > 1.0 {
+                0
+            }
+            else {
+                let sx = ((su + 1.0) / 2.0 * width) as isize;
+                let sy = ((sv + 1.0) / 2.0 * height) as isize;
+                source.get(sx, sy)
+            };
+        }
+    }
+}
+
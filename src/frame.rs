@@ -0,0 +1,116 @@
+use crate::scaler::PIXEL_OFF;
+use crate::filters::shade;
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+#[derive(Clone, Debug)]
+/// A flat buffer of 0xRRGGBB colors, ready to be displayed
+pub struct Frame {
+    /// Colors of the pixels, row after row
+    pub pixels: Vec<u32>,
+    /// Width of the frame
+    pub width: usize,
+    /// Height of the frame
+    pub height: usize,
+}
+
+impl Frame {
+    /// Creates a new black `Frame` object
+    pub fn new(width: usize, height: usize) -> Self {
+        Frame {
+            pixels: vec![PIXEL_OFF; width * height],
+            width: width,
+            height: height,
+        }
+    }
+
+    /// Returns the pixel at (`x`,`y`), clamping the coordinates to the edges of the frame
+    pub fn get(&self, x: isize, y: isize) -> u32 {
+        let x = x.max(0).min(self.width as isize - 1) as usize;
+        let y = y.max(0).min(self.height as isize - 1) as usize;
+        self.pixels[y * self.width + x]
+    }
+
+    /// Sets the pixel at (`x`,`y`) to `color`
+    pub fn set(&mut self, x: usize, y: usize, color: u32) {
+        self.pixels[y * self.width + x] = color;
+    }
+
+    /// Fills the `width` x `height` rectangle with its top-left corner at (`x`,`y`) with `color`,
+    /// clipped to the frame
+    pub fn fill_rect(&mut self, x: usize, y: usize, width: usize, height: usize, color: u32) {
+        let right = (x + width).min(self.width);
+        let bottom = (y + height).min(self.height);
+        for row in y.min(bottom)..bottom {
+            self.pixels[row * self.width + x.min(right)..row * self.width + right].fill(color);
+        }
+    }
+
+    /// Copies `source` into the `width` x `height` rectangle with its top-left corner at (`x`,`y`),
+    /// stretched with the nearest pixel, clipped to the frame
+    pub fn blit_scaled(&mut self, source: &Frame, x: usize, y: usize, width: usize, height: usize) {
+        if source.width == 0 || source.height == 0 {
+            return;
+        }
+        let right = (x + width).min(self.width);
+        let bottom = (y + height).min(self.height);
+        for row in y.min(bottom)..bottom {
+            let source_row = (row - y) * source.height / height;
+            for col in x.min(right)..right {
+                let source_col = (col - x) * source.width / width;
+                self.pixels[row * self.width + col] = source.pixels[source_row * source.width + source_col];
+            }
+        }
+    }
+
+    /// Darkens the `width` x `height` rectangle with its top-left corner at (`x`,`y`) by `amount` (0.0 - 1.0),
+    /// clipped to the frame. Used as a translucent background for the overlays
+    pub fn shade_rect(&mut self, x: usize, y: usize, width: usize, height: usize, amount: f32) {
+        let right = (x + width).min(self.width);
+        let bottom = (y + height).min(self.height);
+        for row in y.min(bottom)..bottom {
+            for pixel in &mut self.pixels[row * self.width + x.min(right)..row * self.width + right] {
+                *pixel = shade(*pixel, 1.0 - amount);
+            }
+        }
+    }
+
+    /// Saves the frame into an uncompressed 24-bit BMP file at `path`
+    pub fn save_bmp(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        // Each row is padded to a multiple of 4 bytes
+        let row_size = (self.width * 3 + 3) & !3;
+        let data_size = row_size * self.height;
+        let mut file = BufWriter::new(File::create(path)?);
+
+        // File header
+        file.write_all(b"BM")?;
+        file.write_all(&(54 + data_size as u32).to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(&54u32.to_le_bytes())?;
+        // Info header: no compression, 1 plane, 24 bits per pixel, 2835 pixels per meter (72 DPI)
+        file.write_all(&40u32.to_le_bytes())?;
+        file.write_all(&(self.width as i32).to_le_bytes())?;
+        file.write_all(&(self.height as i32).to_le_bytes())?;
+        file.write_all(&1u16.to_le_bytes())?;
+        file.write_all(&24u16.to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(&(data_size as u32).to_le_bytes())?;
+        file.write_all(&2835i32.to_le_bytes())?;
+        file.write_all(&2835i32.to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+        file.write_all(&0u32.to_le_bytes())?;
+
+        // Rows bottom to top, pixels in BGR order
+        let mut row = vec![0u8; row_size];
+        for y in (0..self.height).rev() {
+            for (x, &color) in self.pixels[y * self.width..(y + 1) * self.width].iter().enumerate() {
+                row[x * 3..x * 3 + 3].copy_from_slice(&color.to_le_bytes()[..3]);
+            }
+            file.write_all(&row)?;
+        }
+        file.flush()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+use std::thread;
+
+/// Time before a deadline under which the timer spins instead of sleeping,
+/// as the OS sleeps are usually not more precise than this
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+/// Maximum number of frames emulated without being presented when the host falls behind, at normal speed.
+/// Past that, the timer gives up catching up and the emulation slows down
+pub const MAX_FRAME_SKIP: u32 = 5;
+/// Fraction of a frame by which a frame can be emulated early, so the timing jitter
+/// doesn't alternate between 0 and 2 frames per presented frame at normal speed
+const FRAME_TOLERANCE: f64 = 0.1;
+
+#[derive(Debug)]
+/// Paces the main loop at a fixed frame rate
+///
+/// The frames are presented at most at the frame rate, and the timer tells how many frames to emulate
+/// before each one: usually 1, more when the host falls behind and frames must be skipped or when running faster,
+/// and 0 when running slower or with vsync when the display refreshes faster than the frame rate
+pub struct FrameTimer {
+    /// Duration of a frame at normal speed
+    frame_duration: Duration,
+    /// Deadline of the next presented frame
+    next_frame: Instant,
+    /// Last time the frames to emulate were counted
+    last_count: Instant,
+    /// Fraction of a frame elapsed but not emulated yet
+    remainder: f64,
+    /// Speed multiplier, `None` if uncapped
+    speed: Option<f64>,
+    /// Whether the display already paces the loop with vsync, in which case the timer never sleeps
+    vsync: bool,
+}
+
+impl FrameTimer {
+    /// Creates a new `FrameTimer` object running at `frame_rate` frames per second
+    pub fn new(frame_rate: f64, vsync: bool) -> Self {
+        let frame_duration = Duration::from_secs_f64(1.0 / frame_rate);
+        let now = Instant::now();
+        FrameTimer {
+            frame_duration: frame_duration,
+            next_frame: now + frame_duration,
+            last_count: now,
+            remainder: 0.0,
+            speed: Some(1.0),
+            vsync: vsync,
+        }
+    }
+
+    /// Sets the speed multiplier, `None` to run as fast as possible
+    pub fn set_speed(&mut self, speed: Option<f64>) {
+        self.speed = speed;
+        self.remainder = 0.0;
+    }
+
+    /// Whether the speed is uncapped, in which case the frames are emulated as long as `has_time_left` is `true`
+    pub fn is_uncapped(&self) -> bool {
+        self.speed.is_none()
+    }
+
+    /// Whether there is still time to emulate frames before the next frame must be presented
+    pub fn has_time_left(&self) -> bool {
+        Instant::now() < self.next_frame
+    }
+
+    /// Waits for the next frame deadline (unless paced by vsync), then returns the number of frames to emulate
+    pub fn wait(&mut self) -> u32 {
+        if !self.vsync {
+            self.sleep_until(self.next_frame);
+        }
+
+        let now = Instant::now();
+        self.next_frame += self.frame_duration;
+        // The host is too slow, we drop the presentations we can't catch up on
+        if self.next_frame < now {
+            self.next_frame = now + self.frame_duration;
+        }
+
+        let elapsed = (now - self.last_count).as_secs_f64() / self.frame_duration.as_secs_f64();
+        self.last_count = now;
+
+        let speed = match self.speed {
+            Some(speed) => speed,
+            None => return 0,
+        };
+        // Past the allowed frame skip, the lost time is dropped and the emulation slows down
+        let max_frames = (MAX_FRAME_SKIP + 1) as f64 * speed.max(1.0);
+        let due = (self.remainder + elapsed * speed).min(max_frames);
+        let frames = (due + FRAME_TOLERANCE).floor();
+        self.remainder = due - frames;
+        frames as u32
+    }
+
+    /// Sleeps until `deadline`, then spins for the last moments to wake up on time
+    fn sleep_until(&self, deadline: Instant) {
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return;
+            }
+            let remaining = deadline - now;
+            if remaining > SPIN_THRESHOLD {
+                thread::sleep(remaining - SPIN_THRESHOLD);
+            }
+            else {
+                thread::yield_now();
+            }
+        }
+    }
+}
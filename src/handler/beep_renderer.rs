@@ -0,0 +1,141 @@
+use crate::handler::tone::{Tone, ToneConfig};
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Delay between the emulated time of a sound edge and the time it is played, in seconds.
+/// It gives the emulator some time to push the edges before the audio callback needs them
+const LATENCY: f64 = 0.05;
+/// Maximum advance of the emulated time over the audio clock before they are resynchronized, in seconds
+const MAX_DRIFT: f64 = 0.25;
+
+#[derive(Clone, Copy, Debug)]
+/// A change of the sound timer state, timestamped in emulated time
+struct SoundEdge {
+    /// Emulated time of the edge, in seconds
+    time: f64,
+    /// Whether the beep starts (`true`) or stops (`false`)
+    on: bool,
+}
+
+#[derive(Debug)]
+/// Renders the beep into the sample stream from the sound timer edges
+///
+/// The sound timer state changes are timestamped in emulated time and rendered at the exact sample offsets,
+/// so even a beep shorter than the audio buffer is played, with its correct length
+pub struct BeepRenderer {
+    /// Tone generator
+    tone: Tone,
+    /// Duration of a sample, in seconds
+    sample_duration: f64,
+    /// Edges not rendered yet, in chronological order
+    edges: VecDeque<SoundEdge>,
+    /// Emulated time of the next sample, `None` until the first edge is received
+    clock: Option<f64>,
+}
+
+impl BeepRenderer {
+    /// Creates a new `BeepRenderer` object for an output of `sample_rate` samples per second
+    pub fn new(tone_config: ToneConfig, sample_rate: u32) -> Self {
+        BeepRenderer {
+            tone: Tone::new(tone_config, sample_rate),
+            sample_duration: 1.0 / sample_rate as f64,
+            edges: VecDeque::new(),
+            clock: None,
+        }
+    }
+
+    /// Schedules the start (`on` = `true`) or the stop of the beep at the emulated time `time`
+    pub fn push_edge(&mut self, on: bool, time: Duration) {
+        self.edges.push_back(SoundEdge {
+            time: time.as_secs_f64(),
+            on: on,
+        });
+    }
+
+    /// Sets the volume of the beep (0.0 - 1.0)
+    pub fn set_volume(&mut self, volume: f32) {
+        self.tone.set_volume(volume);
+    }
+
+    /// Mutes or unmutes the output
+    pub fn set_muted(&mut self, muted: bool) {
+        self.tone.set_muted(muted);
+    }
+
+    /// Renders the samples up to the emulated time `time` into `out`, without any resynchronization.
+    /// Used to render the beep offline, the first call starting at the emulated time `time`
+    pub fn render_until(&mut self, time: Duration, out: &mut Vec<f32>) {
+        let time = time.as_secs_f64();
+        let mut clock = self.clock.unwrap_or(time);
+
+        while clock < time {
+            while let Some(edge) = self.edges.front() {
+                if edge.time > clock {
+                    break;
+                }
+                self.tone.set_gate(edge.on);
+                self.edges.pop_front();
+            }
+            out.push(self.tone.next_sample());
+            clock += self.sample_duration;
+        }
+        self.clock = Some(clock);
+    }
+
+    /// Moves the clock of an offline rendering to the emulated time `time`, e.g. when a saved state is loaded.
+    /// The pending edges are applied at once, the next samples start from `time`
+    pub fn seek(&mut self, time: Duration) {
+        for edge in self.edges.drain(..) {
+            self.tone.set_gate(edge.on);
+        }
+        self.clock = Some(time.as_secs_f64());
+    }
+
+    /// Fills `out` with the next samples
+    pub fn render(&mut self, out: &mut [f32]) {
+        self.synchronize();
+
+        for x in out.iter_mut() {
+            if let Some(clock) = self.clock {
+                while let Some(edge) = self.edges.front() {
+                    if edge.time > clock {
+                        break;
+                    }
+                    self.tone.set_gate(edge.on);
+                    self.edges.pop_front();
+                }
+                self.clock = Some(clock + self.sample_duration);
+            }
+            *x = self.tone.next_sample();
+        }
+    }
+
+    /// Resynchronizes the audio clock with the emulated time if they drifted apart,
+    /// e.g. when the emulator started, or when it ran slower or faster than real time for a while.
+    /// An edge is never played late, and the recent edges keep their relative timing, so the beeps keep their length.
+    /// The edges older than the newest one minus `LATENCY` are stale (e.g. after an uncapped fast-forward),
+    /// only the gate state they leave is kept
+    fn synchronize(&mut self) {
+        let (first_edge, last_edge) = match (self.edges.front(), self.edges.back()) {
+            (Some(first), Some(last)) => (first.time, last.time),
+            _ => return,
+        };
+
+        let drifted = match self.clock {
+            Some(clock) => first_edge < clock || last_edge > clock + MAX_DRIFT,
+            None => true,
+        };
+        if drifted {
+            while let Some(edge) = self.edges.front() {
+                if edge.time >= last_edge - LATENCY {
+                    break;
+                }
+                self.tone.set_gate(edge.on);
+                self.edges.pop_front();
+            }
+            let next_edge = self.edges.front().map_or(last_edge, |edge| edge.time);
+            self.clock = Some(next_edge - LATENCY);
+        }
+    }
+}
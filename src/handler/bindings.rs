@@ -0,0 +1,181 @@
+use crate::handler::keyboard_trait::Command;
+
+use std::error::Error;
+
+/// Names of the emulator actions, used to bind them, and their commands
+const ACTIONS: [(&str, Command); 28] = [
+    ("quit", Command::Quit),
+    ("mute", Command::ToggleMute),
+    ("volume-up", Command::VolumeUp),
+    ("volume-down", Command::VolumeDown),
+    ("record-audio", Command::ToggleRecording),
+    ("pause", Command::TogglePause),
+    ("frame-advance", Command::FrameAdvance),
+    ("step", Command::StepInstruction),
+    ("fast-forward", Command::HoldFastForward),
+    ("toggle-fast-forward", Command::ToggleFastForward),
+    ("slow-motion", Command::ToggleSlowMotion),
+    ("speed-up", Command::SpeedUp),
+    ("speed-down", Command::SpeedDown),
+    ("reset", Command::Reset),
+    ("open-rom", Command::OpenRom),
+    ("osd", Command::ToggleOsd),
+    ("keypad-overlay", Command::ToggleKeypadOverlay),
+    ("record-macro", Command::ToggleMacroRecording),
+    ("play-macro", Command::PlayMacro),
+    ("save-state", Command::SaveState),
+    ("load-state", Command::LoadState),
+    ("screenshot", Command::Screenshot),
+    ("fullscreen", Command::ToggleFullscreen),
+    ("debug-output", Command::ToggleDebug),
+    ("menu-up", Command::MenuUp),
+    ("menu-down", Command::MenuDown),
+    ("menu-select", Command::MenuSelect),
+    ("menu-back", Command::MenuBack),
+];
+
+/// Default bindings, with the same syntax as the `bindings` option
+const DEFAULT_BINDINGS: &str = "quit=escape, mute=m, volume-up== kpplus, volume-down=- kpminus, record-audio=f9, \
+    pause=p, frame-advance=n, step=i, fast-forward=tab, toggle-fast-forward=f6, slow-motion=f7, \
+    speed-up=pageup, speed-down=pagedown, reset=f1, open-rom=f2, osd=f3, keypad-overlay=f4, \
+    record-macro=f10, play-macro=f11, save-state=f5, load-state=f8, screenshot=f12, fullscreen=alt+enter, \
+    debug-output=ctrl+d, menu-up=up, menu-down=down, menu-select=enter kpenter right, menu-back=backspace left";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Modifier keys held with a key
+pub struct Modifiers {
+    /// Left or right Ctrl
+    pub ctrl: bool,
+    /// Left or right Shift
+    pub shift: bool,
+    /// Left or right Alt
+    pub alt: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// A key and the modifiers held with it
+struct Hotkey {
+    /// Name of the key, see `key_name`
+    key: String,
+    /// Modifiers that must be held, no other modifier may be held
+    modifiers: Modifiers,
+}
+
+impl Hotkey {
+    /// Parses a hotkey like `ctrl+shift+s`, `f5` or `ctrl++`
+    fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let s = s.trim().to_lowercase();
+        // The key itself may be `+`
+        let (mods, key) = match s.strip_suffix("++") {
+            Some(mods) => (mods, "+"),
+            None => match s.rfind('+') {
+                Some(i) => (&s[..i], &s[i + 1..]),
+                None => ("", s.as_str()),
+            },
+        };
+        if key.is_empty() {
+            return Err(format!("Missing key in binding: {}", s).into());
+        }
+
+        let mut modifiers = Modifiers::default();
+        for modifier in mods.split('+').filter(|m| !m.is_empty()) {
+            match modifier {
+                "ctrl" => modifiers.ctrl = true,
+                "shift" => modifiers.shift = true,
+                "alt" => modifiers.alt = true,
+                _ => return Err(format!("Unknown modifier: {}", modifier).into()),
+            }
+        }
+        Ok(Hotkey {
+            key: key_name(key),
+            modifiers: modifiers,
+        })
+    }
+}
+
+/// Returns the common name of a key, given its SDL or minifb name.
+/// Both backends name most keys the same way, only the differences are handled here
+pub fn key_name(name: &str) -> String {
+    let name = name.to_lowercase().replace(' ', "");
+    let name = match name.as_str() {
+        "return" => "enter",
+        "equals" | "equal" => "=",
+        "minus" => "-",
+        "keypad+" | "numpadplus" => "kpplus",
+        "keypad-" | "numpadminus" => "kpminus",
+        "keypadenter" | "numpadenter" => "kpenter",
+        name => name,
+    };
+    // minifb names the digit keys `Key1`...
+    match name.strip_prefix("key") {
+        Some(digit) if digit.len() == 1 => digit.to_string(),
+        _ => name.to_string(),
+    }
+}
+
+#[derive(Clone, Debug)]
+/// Table binding the keys, with modifiers, to the emulator commands.
+/// The backends only name the keys, the commands are looked up here
+pub struct Bindings {
+    /// Hotkeys and their command, a hotkey being bound to one command at most
+    bindings: Vec<(Hotkey, Command)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let mut bindings = Bindings { bindings: Vec::new() };
+        // The default bindings are known to be valid
+        bindings.bind(DEFAULT_BINDINGS).unwrap_or_default();
+        bindings
+    }
+}
+
+impl Bindings {
+    /// Returns the default bindings, changed by `s`: a comma-separated list of `action=hotkeys`,
+    /// the hotkeys of an action being space-separated, e.g. `save-state=ctrl+s f5, quit=q`
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut bindings = Bindings::default();
+        bindings.bind(s)?;
+        Ok(bindings)
+    }
+
+    /// Binds the hotkeys of each `action=hotkeys` of `s`, replacing the hotkeys the actions had
+    fn bind(&mut self, s: &str) -> Result<(), Box<dyn Error>> {
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (action, hotkeys) = entry.split_at(entry.find('=').ok_or(format!("Invalid binding: {}", entry))?);
+            let action = action.trim().to_lowercase();
+            let command = ACTIONS.iter()
+                .find(|(name, _)| *name == action)
+                .map(|(_, command)| command.clone())
+                .ok_or(format!("Unknown action: {}", action))?;
+
+            self.bindings.retain(|(_, c)| *c != command);
+            for hotkey in hotkeys[1..].split_whitespace() {
+                let hotkey = Hotkey::parse(hotkey)?;
+                self.bindings.retain(|(h, _)| *h != hotkey);
+                self.bindings.push((hotkey, command.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the command bound to the key named `key` pressed with `modifiers`, `None` if the key isn't a hotkey
+    pub fn command(&self, key: &str, modifiers: Modifiers) -> Option<Command> {
+        let key = key_name(key);
+        self.bindings.iter()
+            .find(|(h, _)| h.key == key && h.modifiers == modifiers)
+            .map(|(_, command)| command.clone())
+    }
+
+    /// Returns the command for the release of the key named `key`, for the commands acting while the key is held.
+    /// The modifiers are ignored, they may be released first
+    pub fn release_command(&self, key: &str) -> Option<Command> {
+        let key = key_name(key);
+        self.bindings.iter()
+            .filter(|(h, _)| h.key == key)
+            .find_map(|(_, command)| match command {
+                Command::HoldFastForward => Some(Command::ReleaseFastForward),
+                _ => None,
+            })
+    }
+}
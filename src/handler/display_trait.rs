@@ -1,10 +1,24 @@
 use std::error::Error;
 use std::fmt::Debug;
 
+/// Default title of the emulator window
+pub const WINDOW_TITLE: &str = "Yet Another CHIP-8 Emulator";
+
 /// Display functions that a handler must implement
+///
+/// `pixels` is a flat buffer of `width` * `height` 0xRRGGBB colors, row after row
 pub trait DisplayTrait: Debug {
     /// Updates the screen to display new changes
-    fn update(&mut self, pixels: &Vec<Vec<u8>>) -> Result<(), Box<dyn Error>>;
+    fn update(&mut self, pixels: &[u32], width: usize, height: usize) -> Result<(), Box<dyn Error>>;
+    /// Called instead of `update` when nothing changed since the last update.
+    /// Nothing is presented, but the window must stay responsive
+    fn idle(&mut self) -> Result<(), Box<dyn Error>>;
+    /// Whether presenting a frame waits for the vertical sync, in which case the display paces the main loop
+    fn vsync(&self) -> bool;
+    /// Sets the title of the window
+    fn set_title(&mut self, title: &str) -> Result<(), Box<dyn Error>>;
+    /// Switches the window to fullscreen (`fullscreen` = `true`) or back to windowed
+    fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), Box<dyn Error>>;
     /// Draws the `pixels` to the active window / canvas. Does not refresh the screen however
-    fn draw(&mut self, pixels: &Vec<Vec<u8>>) -> Result<(), Box<dyn Error>>;
+    fn draw(&mut self, pixels: &[u32], width: usize, height: usize) -> Result<(), Box<dyn Error>>;
 }
\ No newline at end of file
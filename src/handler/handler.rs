@@ -1,11 +1,11 @@
 use crate::chip8::Chip8Config;
-use crate::handler::display_trait::{DisplayTrait};
+use crate::handler::display_trait::{DisplayTrait, WINDOW_TITLE};
 use crate::handler::keyboard_trait::KeyboardTrait;
 use crate::handler::sound_trait::SoundTrait;
 use crate::handler::minifb::{MiniFbKeyboard, MiniFbDisplay, MiniFbSound};
 use crate::handler::sdl::{SdlKeyboard, SdlDisplay, SdlSound};
 
-use std::time::{Duration};
+use std::env;
 use std::error::Error;
 use std::rc::Rc;
 use std::cell::RefCell;
@@ -33,45 +33,57 @@ pub enum HandlerType {
 impl Handler {
     /// Creates a new `Handler` object
     pub fn new(chip8_config: Chip8Config) -> Result<Self, Box<dyn Error>> {
-        let display_rate = Duration::from_secs_f64(1.0 / chip8_config.hertz);
-
         match chip8_config.handler_type {
             HandlerType::MINIFB => {
                 let mut window = Window::new(
-                    "Yet Another CHIP-8 Emulator",
+                    WINDOW_TITLE,
                     chip8_config.window_width,
                     chip8_config.window_height,
                     WindowOptions::default(),
                 )?;
 
-                // Sets the refresh rate
-                // minifb will check how much time has passed since the last time
-                // and if it's less than the selected time it will sleep for the remainder of it.
-                // minifb defaults to 4ms if not specified (quite slow)
-                window.limit_update_rate(Some(display_rate));
+                // The main loop is paced by its frame timer, minifb must not sleep on its own
+                // (it defaults to 4ms between updates otherwise)
+                window.limit_update_rate(None);
 
                 // We share a mutable reference to the window.
                 // This is safe because the application is single-threaded and we only use the mutable aspect of the window in MiniFbDisplay
                 let window = Rc::new(RefCell::new(window));
 
                 Ok(Handler {
-                    keyboard: Box::new(MiniFbKeyboard::new(Rc::clone(&window))),
+                    keyboard: Box::new(MiniFbKeyboard::new(Rc::clone(&window), chip8_config.bindings)),
                     display: Box::new(MiniFbDisplay::new(Rc::clone(&window))),
-                    sound: Box::new(MiniFbSound::new()),
+                    sound: Box::new(MiniFbSound::new(chip8_config.tone)),
                 })
             },
             HandlerType::SDL => {
+                // SDL only reads the driver hints since 2.0.22, older versions only read the environment variables.
+                // Both are set before SDL is initialized, the hints being a fallback
+                if let Some(driver) = &chip8_config.video_driver {
+                    env::set_var("SDL_VIDEODRIVER", driver);
+                    sdl2::hint::set("SDL_VIDEODRIVER", driver);
+                    // Offscreen drivers are mostly used in CI, where there is usually no sound card either
+                    if (driver == "dummy" || driver == "offscreen") && env::var_os("SDL_AUDIODRIVER").is_none() {
+                        env::set_var("SDL_AUDIODRIVER", "dummy");
+                        sdl2::hint::set("SDL_AUDIODRIVER", "dummy");
+                    }
+                }
                 let sdl = sdl2::init()?;
 
                 Ok(Handler {
-                    keyboard: Box::new(SdlKeyboard::new(&sdl)?),
+                    keyboard: Box::new(SdlKeyboard::new(
+                        &sdl,
+                        chip8_config.window_width,
+                        chip8_config.window_height,
+                        chip8_config.bindings
+                    )?),
                     display: Box::new(SdlDisplay::new(
                         &sdl,
-                        display_rate,
                         chip8_config.window_width,
-                        chip8_config.window_height
+                        chip8_config.window_height,
+                        chip8_config.vsync
                     )?),
-                    sound: Box::new(SdlSound::new(&sdl)?),
+                    sound: Box::new(SdlSound::new(&sdl, chip8_config.tone)?),
                 })
             },
         }
@@ -1,9 +1,87 @@
+use crate::keypad::KeyEvent;
+
 use std::fmt::Debug;
 
+#[derive(Clone, Debug, PartialEq)]
+/// Emulator commands, triggered by hotkeys (default hotkeys in parentheses, see `Bindings`)
+pub enum Command {
+    /// Quits the emulator, or goes back to the launcher if the ROM was launched from it (Esc)
+    Quit,
+    /// Quits the emulator (closing the window)
+    Close,
+    /// The window lost the focus or was minimized
+    FocusLost,
+    /// The window got the focus back or was restored
+    FocusGained,
+    /// Mutes / unmutes the sound (M)
+    ToggleMute,
+    /// Turns the volume up (=)
+    VolumeUp,
+    /// Turns the volume down (-)
+    VolumeDown,
+    /// Starts / stops recording the sound into a WAV file (F9)
+    ToggleRecording,
+    /// Pauses / resumes the emulation (P)
+    TogglePause,
+    /// Runs exactly one frame while paused (N)
+    FrameAdvance,
+    /// Runs exactly one instruction while paused (I)
+    StepInstruction,
+    /// Starts fast-forwarding while the key is held (Tab)
+    HoldFastForward,
+    /// Stops the fast-forward started by holding the key (Tab released)
+    ReleaseFastForward,
+    /// Turns the fast-forward on / off (F6)
+    ToggleFastForward,
+    /// Turns the slow motion on / off (F7)
+    ToggleSlowMotion,
+    /// Runs more instructions per frame (Page Up)
+    SpeedUp,
+    /// Runs fewer instructions per frame (Page Down)
+    SpeedDown,
+    /// Resets the VM and restarts the loaded ROM (F1)
+    Reset,
+    /// Picks another ROM to run with a file dialog (F2)
+    OpenRom,
+    /// Switches the on-screen display between off, messages, and messages with stats (F3)
+    ToggleOsd,
+    /// Shows / hides the keypad over the screen (F4)
+    ToggleKeypadOverlay,
+    /// Starts / stops recording the input macro (F10)
+    ToggleMacroRecording,
+    /// Plays / stops the input macro (F11)
+    PlayMacro,
+    /// Saves the state of the VM (F5)
+    SaveState,
+    /// Restores the saved state of the VM (F8)
+    LoadState,
+    /// Saves the screen into a BMP file (F12)
+    Screenshot,
+    /// Switches between fullscreen and windowed (Alt+Enter)
+    ToggleFullscreen,
+    /// Turns the debugging output on / off (Ctrl+D)
+    ToggleDebug,
+    /// Runs the ROM at the given path, e.g. dropped onto the window
+    LoadRom(String),
+    /// Selects the previous entry of the launcher (Up)
+    MenuUp,
+    /// Selects the next entry of the launcher (Down)
+    MenuDown,
+    /// Opens the selected entry of the launcher (Enter, Right)
+    MenuSelect,
+    /// Goes back to the parent directory in the launcher (Backspace, Left)
+    MenuBack,
+}
+
 /// Keyboard functions that a handler must implement
 pub trait KeyboardTrait: Debug {
-    /// Update `keys_state`, `true` if key is pressed, `false` if key is released
-    fn update_keys_state(&mut self, keys_state: &mut [bool]) -> bool;
+    /// Pushes the CHIP-8 key presses and releases since the last update into `events`, with the time they happened at
+    ///
+    /// Returns the emulator commands triggered since the last update
+    fn poll_events(&mut self, events: &mut Vec<KeyEvent>) -> Vec<Command>;
+    /// Returns the positions currently pressed with the mouse (left button held) or by touch,
+    /// as of the last update, in window coordinates from 0.0 to 1.0
+    fn pointers(&self) -> Vec<(f32, f32)>;
 }
 
 
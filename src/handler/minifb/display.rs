@@ -20,37 +20,36 @@ impl MiniFbDisplay {
             window: window,
         }
     }
-
-    /// Converts the binary screen (0 or 1) to a color screen (black / white)
-    /// Also converts the Vec<Vec<u8>> to a Vec<u32> for the update_with_buffer minifb function
-    pub fn color_screen(&self, pixels: &Vec<Vec<u8>>) -> Vec<u32> {
-        let v = pixels.iter().map(|p| {
-            p.iter().map(|b| {
-                if *b == 1 {
-                    0xffffff
-                }
-                else {
-                    0x0
-                }
-            }).collect::<Vec<u32>>()
-        }).collect::<Vec<Vec<u32>>>();
-        v.concat()
-    }
 }
 
 impl DisplayTrait for MiniFbDisplay {
-    fn update(&mut self, pixels: &Vec<Vec<u8>>) -> Result<(), Box<dyn Error>> {
-        let colored_flat_pixels = self.color_screen(pixels);
-        self.window.borrow_mut().update_with_buffer(
-            &colored_flat_pixels,
-            pixels.get(0).ok_or("Empty pixels vector")?.len(),
-            pixels.len()
-        )?;
+    fn update(&mut self, pixels: &[u32], width: usize, height: usize) -> Result<(), Box<dyn Error>> {
+        self.window.borrow_mut().update_with_buffer(pixels, width, height)?;
+        Ok(())
+    }
+
+    /// Only processes the window events, the last buffer stays displayed
+    fn idle(&mut self) -> Result<(), Box<dyn Error>> {
+        self.window.borrow_mut().update();
+        Ok(())
+    }
+
+    fn set_title(&mut self, title: &str) -> Result<(), Box<dyn Error>> {
+        self.window.borrow_mut().set_title(title);
         Ok(())
     }
 
+    fn set_fullscreen(&mut self, _fullscreen: bool) -> Result<(), Box<dyn Error>> {
+        Err("Fullscreen isn't supported by minifb".into())
+    }
+
+    /// minifb doesn't sync with the display refresh, the main loop is paced by its frame timer
+    fn vsync(&self) -> bool {
+        false
+    }
+
     /// We don't need to draw anything with minifb, only the pixels are required
-    fn draw(&mut self, _pixels: &Vec<Vec<u8>>) -> Result<(), Box<dyn Error>> {
+    fn draw(&mut self, _pixels: &[u32], _width: usize, _height: usize) -> Result<(), Box<dyn Error>> {
         Ok(())
     }
 }
@@ -1,22 +1,31 @@
-use crate::handler::keyboard_trait::KeyboardTrait;
+use crate::handler::keyboard_trait::{KeyboardTrait, Command};
+use crate::handler::bindings::{Bindings, Modifiers};
+use crate::keypad::KeyEvent;
 
 use std::rc::Rc;
 use std::cell::RefCell;
+use std::time::Instant;
 
-use minifb::{Window, Key, KeyRepeat};
+use minifb::{Window, Key, KeyRepeat, MouseButton, MouseMode};
 
 #[derive(Debug)]
 /// The keyboard component, handling the keystrokes
 pub struct MiniFbKeyboard {
     /// Window handling all the events (display + keyboard)
     pub window: Rc<RefCell<Window>>,
+    /// Hotkeys of the emulator commands
+    bindings: Bindings,
+    /// Whether the window had the focus at the last update
+    active: bool,
 }
 
 impl MiniFbKeyboard {
     /// Creates a new `Keypad` object
-    pub fn new(window: Rc<RefCell<Window>>) -> Self {
+    pub fn new(window: Rc<RefCell<Window>>, bindings: Bindings) -> Self {
         MiniFbKeyboard {
             window,
+            bindings: bindings,
+            active: true,
         }
     }
 
@@ -46,31 +55,74 @@ impl MiniFbKeyboard {
             _ => 0xFF,
         }
     }
+
+    /// Returns the modifiers currently held
+    fn modifiers(&self) -> Modifiers {
+        let window = self.window.borrow();
+        Modifiers {
+            ctrl: window.is_key_down(Key::LeftCtrl) || window.is_key_down(Key::RightCtrl),
+            shift: window.is_key_down(Key::LeftShift) || window.is_key_down(Key::RightShift),
+            alt: window.is_key_down(Key::LeftAlt) || window.is_key_down(Key::RightAlt),
+        }
+    }
 }
 
 impl KeyboardTrait for MiniFbKeyboard {
-    fn update_keys_state(&mut self, keys_state: &mut [bool]) -> bool {
-        if !self.window.borrow().is_open() || self.window.borrow().is_key_down(Key::Escape) {
-            return false;
+    /// minifb doesn't timestamp the events, they get the time of the update
+    fn poll_events(&mut self, events: &mut Vec<KeyEvent>) -> Vec<Command> {
+        if !self.window.borrow().is_open() {
+            return vec![Command::Close];
+        }
+
+        let mut commands = Vec::new();
+        let time = Instant::now();
+        let modifiers = self.modifiers();
+
+        // minifb has no focus events, a minimized window isn't active either
+        let active = self.window.borrow_mut().is_active();
+        if active != self.active {
+            commands.push(if active { Command::FocusGained } else { Command::FocusLost });
+            self.active = active;
         }
 
         self.window.borrow().get_keys_pressed(KeyRepeat::No).map(|keys| {
             for t in keys {
+                if let Some(command) = self.bindings.command(&format!("{:?}", t), modifiers) {
+                    commands.push(command);
+                    continue;
+                }
                 let k = MiniFbKeyboard::convert_keycode(t);
                 if k != 0xFF {
-                    keys_state[k as usize] = true;
+                    events.push(KeyEvent { key: k, pressed: true, time: time });
                 }
             }
         });
         self.window.borrow().get_keys_released().map(|keys| {
             for t in keys {
+                if let Some(command) = self.bindings.release_command(&format!("{:?}", t)) {
+                    commands.push(command);
+                    continue;
+                }
                 let k = MiniFbKeyboard::convert_keycode(t);
                 if k != 0xFF {
-                    keys_state[k as usize] = false;
+                    events.push(KeyEvent { key: k, pressed: false, time: time });
                 }
             }
         });
 
-        true
+        commands
+    }
+
+    /// minifb has no touch events, but touchscreens usually emulate the mouse
+    fn pointers(&self) -> Vec<(f32, f32)> {
+        let window = self.window.borrow();
+        if !window.get_mouse_down(MouseButton::Left) {
+            return Vec::new();
+        }
+        let (width, height) = window.get_size();
+        match window.get_mouse_pos(MouseMode::Discard) {
+            Some((x, y)) if width > 0 && height > 0 => vec![(x / width as f32, y / height as f32)],
+            _ => Vec::new(),
+        }
     }
 }
@@ -1,23 +1,135 @@
 use crate::handler::sound_trait::SoundTrait;
+use crate::handler::tone::ToneConfig;
+use crate::handler::beep_renderer::BeepRenderer;
 use crate::utils::log_warning;
 
-#[derive(Debug)]
-/// Empty sound component, minifb doesn't handle sound
-pub struct MiniFbSound {}
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use core::fmt;
+
+use cpal::{Sample, SampleFormat, Stream, StreamConfig};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+
+/// Beep renderer shared between the sound component and the audio stream
+type SharedRenderer = Arc<Mutex<BeepRenderer>>;
+
+/// Sound component for minifb
+///
+/// minifb doesn't handle sound, so the beep is played through cpal.
+/// If no audio device is available, the component is a null sink and the beeps are silently dropped
+pub struct MiniFbSound {
+    /// Output stream, constantly running. `None` if no audio device is available
+    stream: Option<Stream>,
+    /// Beep renderer, shared with the stream
+    renderer: Option<SharedRenderer>,
+}
 
 impl MiniFbSound {
     /// Creates a new `MiniFbSound` object
-    pub fn new() -> Self {
-        MiniFbSound{}
+    pub fn new(tone_config: ToneConfig) -> Self {
+        match MiniFbSound::open_stream(tone_config) {
+            Ok((stream, renderer)) => MiniFbSound {
+                stream: Some(stream),
+                renderer: Some(renderer),
+            },
+            Err(e) => {
+                log_warning(format!("No audio device available, sound is disabled: {}", e));
+                MiniFbSound {
+                    stream: None,
+                    renderer: None,
+                }
+            }
+        }
+    }
+
+    /// Opens an output stream on the default audio device
+    fn open_stream(tone_config: ToneConfig) -> Result<(Stream, SharedRenderer), Box<dyn Error>> {
+        let device = cpal::default_host()
+            .default_output_device()
+            .ok_or("No default output device")?;
+        let supported_config = device.default_output_config()?;
+        let sample_format = supported_config.sample_format();
+        let config: StreamConfig = supported_config.into();
+        let renderer = Arc::new(Mutex::new(BeepRenderer::new(tone_config, config.sample_rate.0)));
+
+        let stream = match sample_format {
+            SampleFormat::F32 => MiniFbSound::build_stream::<f32>(&device, &config, Arc::clone(&renderer))?,
+            SampleFormat::I16 => MiniFbSound::build_stream::<i16>(&device, &config, Arc::clone(&renderer))?,
+            SampleFormat::U16 => MiniFbSound::build_stream::<u16>(&device, &config, Arc::clone(&renderer))?,
+        };
+        stream.play()?;
+
+        Ok((stream, renderer))
+    }
+
+    /// Builds a stream playing the samples of `renderer`
+    fn build_stream<T: Sample>(device: &cpal::Device, config: &StreamConfig, renderer: SharedRenderer) -> Result<Stream, Box<dyn Error>> {
+        let channels = config.channels as usize;
+        let mut samples = Vec::new();
+        // A failing stream may report an error on every callback, only the first one is logged
+        let error_logged = AtomicBool::new(false);
+
+        let stream = device.build_output_stream(
+            config,
+            move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+                samples.resize(data.len() / channels, 0.0);
+                match renderer.lock() {
+                    Ok(mut r) => r.render(&mut samples),
+                    Err(_) => return,
+                }
+                // The same sample is written on every channel of a frame
+                for (frame, value) in data.chunks_mut(channels).zip(samples.iter()) {
+                    for sample in frame.iter_mut() {
+                        *sample = Sample::from::<f32>(value);
+                    }
+                }
+            },
+            move |e| {
+                if !error_logged.swap(true, Ordering::Relaxed) {
+                    log_warning(format!("Audio stream error, the next ones are not logged: {}", e));
+                }
+            },
+        )?;
+
+        Ok(stream)
+    }
+
+    /// Runs `f` on the beep renderer, does nothing if there is no audio device
+    fn with_renderer<F: FnOnce(&mut BeepRenderer)>(&self, f: F) {
+        if let Some(renderer) = &self.renderer {
+            if let Ok(mut renderer) = renderer.lock() {
+                f(&mut renderer);
+            }
+        }
     }
 }
 
 impl SoundTrait for MiniFbSound {
-    /// minifb doesn't handle sound
-    fn play_beep(&self) {
-        log_warning("MiniFb doesn't handle sound");
+    fn play_beep(&mut self, timestamp: Duration) {
+        self.with_renderer(|r| r.push_edge(true, timestamp));
+    }
+
+    fn stop_beep(&mut self, timestamp: Duration) {
+        self.with_renderer(|r| r.push_edge(false, timestamp));
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.with_renderer(|r| r.set_volume(volume));
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.with_renderer(|r| r.set_muted(muted));
     }
+}
 
-    /// minifb doesn't handle sound
-    fn stop_beep(&self) {}
-}
\ No newline at end of file
+/// Mock Debug implementation for debugging purpose
+impl fmt::Debug for MiniFbSound {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MiniFbSound")
+            .field("has_device", &self.stream.is_some())
+            .finish()
+    }
+}
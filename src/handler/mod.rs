@@ -2,8 +2,18 @@ mod handler;
 mod display_trait;
 mod keyboard_trait;
 mod sound_trait;
+mod tone;
+mod beep_renderer;
+mod wav_recorder;
+mod bindings;
 
 mod minifb;
 mod sdl;
 
-pub use handler::{Handler, HandlerType};
\ No newline at end of file
+pub use handler::{Handler, HandlerType};
+pub use display_trait::WINDOW_TITLE;
+pub use keyboard_trait::Command;
+pub use sound_trait::SoundTrait;
+pub use tone::{ToneConfig, Waveform};
+pub use wav_recorder::WavRecorder;
+pub use bindings::Bindings;
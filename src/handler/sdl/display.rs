@@ -1,95 +1,156 @@
-use crate::handler::display_trait::DisplayTrait;
+use crate::handler::display_trait::{DisplayTrait, WINDOW_TITLE};
+use crate::utils::log_warning;
 
 use std::error::Error;
-use std::time::{Instant, Duration};
-use std::thread;
 
 use core::fmt;
 
-use sdl2::{Sdl};
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-use sdl2::rect::{Rect};
-use sdl2::pixels::Color;
-
-const SCALE_FACTOR: u32 = 10;
+use sdl2::{Sdl, VideoSubsystem};
+use sdl2::render::{Canvas, Texture};
+use sdl2::video::{FullscreenType, Window};
+use sdl2::pixels::{Color, PixelFormatEnum};
 
 /// Display component for SDL
 pub struct SdlDisplay {
-    /// Display rate
-    display_rate: Duration,
-    /// Last time the display was updated
-    prev_time: Instant,
     /// Canvas handling all the display events
     canvas: Canvas<Window>,
+    /// Streaming texture the frames are uploaded to, created on the first update
+    texture: Option<Texture>,
+    /// Size of the texture, recreated if the frame size changes
+    texture_size: (u32, u32),
+    /// Whether the renderer presents with vsync
+    vsync: bool,
 }
 
 impl SdlDisplay {
     /// Creates a new `SdlDisplay` object
-    pub fn new(sdl: &Sdl, display_frequency: Duration, window_width: usize, window_height: usize) -> Result<Self, Box<dyn Error>> {
+    ///
+    /// Uses the accelerated renderer if available, falls back to the software renderer otherwise.
+    /// If `vsync` is set, presents with vsync when the renderer supports it
+    pub fn new(sdl: &Sdl, window_width: usize, window_height: usize, vsync: bool) -> Result<Self, Box<dyn Error>> {
         let video_subsystem = sdl.video()?;
 
-        let window = video_subsystem
-            .window("Yet Another CHIP-8 Emulator", window_width as u32, window_height as u32)
-            .position_centered()
-            .opengl()
-            .build()?;
+        // The texture is stretched to the window, we want big blocky pixels
+        sdl2::hint::set("SDL_RENDER_SCALE_QUALITY", "nearest");
 
-        let mut canvas = window
+        let mut builder = SdlDisplay::build_window(&video_subsystem, window_width, window_height)?
             .into_canvas()
-            .build()?;
-        canvas.clear();
+            .accelerated();
+        if vsync {
+            builder = builder.present_vsync();
+        }
+        let mut canvas = match builder.build() {
+            Ok(canvas) => canvas,
+            Err(e) => {
+                log_warning(format!("Accelerated renderer unavailable ({}), falling back to the software renderer", e));
+                let mut builder = SdlDisplay::build_window(&video_subsystem, window_width, window_height)?
+                    .into_canvas()
+                    .software();
+                if vsync {
+                    builder = builder.present_vsync();
+                }
+                builder.build()?
+            }
+        };
 
-        canvas.present();
+        // The renderer may not support vsync, the frame timer then paces the main loop
+        let vsync_flag = sdl2::sys::SDL_RendererFlags::SDL_RENDERER_PRESENTVSYNC as u32;
+        let has_vsync = canvas.info().flags & vsync_flag != 0;
+        if vsync && !has_vsync {
+            log_warning("Vsync unavailable, falling back to the frame timer");
+        }
+
+        // In fullscreen, the frames keep their aspect ratio, and the mouse positions stay in window coordinates
+        canvas.set_logical_size(window_width as u32, window_height as u32)?;
 
-        Ok(SdlDisplay {
-            display_rate: display_frequency,
-            prev_time: Instant::now(),
+        let mut display = SdlDisplay {
             canvas: canvas,
-        })
+            texture: None,
+            texture_size: (0, 0),
+            vsync: has_vsync,
+        };
+        display.clear();
+        display.canvas.present();
+
+        Ok(display)
     }
 
-    /// Update the refresh rate, sleeping for the remaining time if necessary
-    fn update_rate(&mut self) {
-        let delta = self.prev_time.elapsed();
-        if delta < self.display_rate {
-            let sleep_time = self.display_rate - delta;
-            thread::sleep(sleep_time);
-        }
-        self.prev_time = Instant::now();
+    /// Creates the emulator window
+    fn build_window(video_subsystem: &VideoSubsystem, window_width: usize, window_height: usize) -> Result<Window, Box<dyn Error>> {
+        let window = video_subsystem
+            .window(WINDOW_TITLE, window_width as u32, window_height as u32)
+            .position_centered()
+            .build()?;
+        Ok(window)
     }
 
     fn clear(&mut self) {
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
         self.canvas.clear();
     }
 }
 
 impl DisplayTrait for SdlDisplay {
-    fn update(&mut self, pixels: &Vec<Vec<u8>>) -> Result<(), Box<dyn Error>> {
-        self.update_rate();
-        self.draw(pixels)?;
+    fn update(&mut self, pixels: &[u32], width: usize, height: usize) -> Result<(), Box<dyn Error>> {
+        self.draw(pixels, width, height)?;
         self.canvas.present();
         Ok(())
     }
 
-    fn draw(&mut self, pixels: &Vec<Vec<u8>>) -> Result<(), Box<dyn Error>> {
-        self.clear();
+    /// The canvas keeps the last frame, and the events are processed by the keyboard component.
+    /// With vsync, the last frame is presented again so the main loop stays paced by the display
+    fn idle(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.vsync {
+            self.clear();
+            if let Some(texture) = &self.texture {
+                self.canvas.copy(texture, None, None)?;
+            }
+            self.canvas.present();
+        }
+        Ok(())
+    }
 
-        for (y, row) in pixels.iter().enumerate() {
-            for (x, &col) in row.iter().enumerate()  {
-                let coord_x = x as u32 * SCALE_FACTOR;
-                let coord_y = y as u32 * SCALE_FACTOR;
+    fn set_title(&mut self, title: &str) -> Result<(), Box<dyn Error>> {
+        self.canvas.window_mut().set_title(title)?;
+        Ok(())
+    }
 
-                if col == 0 {
-                    self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-                }
-                else if col == 1 {
-                    self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-                }
+    /// Uses the desktop resolution, so the display mode doesn't change
+    fn set_fullscreen(&mut self, fullscreen: bool) -> Result<(), Box<dyn Error>> {
+        let mode = if fullscreen { FullscreenType::Desktop } else { FullscreenType::Off };
+        self.canvas.window_mut().set_fullscreen(mode)?;
+        Ok(())
+    }
+
+    fn vsync(&self) -> bool {
+        self.vsync
+    }
 
-                self.canvas.fill_rect(Rect::new(coord_x as i32, coord_y as i32, SCALE_FACTOR, SCALE_FACTOR))?;
+    /// Uploads the frame to the streaming texture, then lets SDL stretch it to the window
+    fn draw(&mut self, pixels: &[u32], width: usize, height: usize) -> Result<(), Box<dyn Error>> {
+        let size = (width as u32, height as u32);
+        if self.texture.is_none() || self.texture_size != size {
+            if let Some(texture) = self.texture.take() {
+                // The texture was created by our canvas, which is still alive, and is never used again
+                unsafe { texture.destroy(); }
             }
+            // RGB888 is a 32-bit 0x00RRGGBB format, the same as our frames
+            self.texture = Some(self.canvas.create_texture_streaming(PixelFormatEnum::RGB888, size.0, size.1)?);
+            self.texture_size = size;
         }
+        self.clear();
+        let texture = self.texture.as_mut().ok_or("Missing texture")?;
+
+        texture.with_lock(None, |buffer: &mut [u8], pitch: usize| {
+            for (y, row) in pixels.chunks(width).enumerate() {
+                let line = &mut buffer[y * pitch..y * pitch + width * 4];
+                for (dst, &color) in line.chunks_mut(4).zip(row.iter()) {
+                    dst.copy_from_slice(&color.to_ne_bytes());
+                }
+            }
+        })?;
+
+        self.canvas.copy(texture, None, None)?;
         Ok(())
     }
 }
@@ -98,8 +159,8 @@ impl DisplayTrait for SdlDisplay {
 impl fmt::Debug for SdlDisplay {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SdlDisplay")
-            .field("display_rate", &self.display_rate)
-            .field("prev_time", &self.prev_time)
+            .field("texture_size", &self.texture_size)
+            .field("vsync", &self.vsync)
             .finish()
     }
 }
@@ -1,27 +1,61 @@
-use crate::handler::keyboard_trait::KeyboardTrait;
+use crate::handler::keyboard_trait::{KeyboardTrait, Command};
+use crate::handler::bindings::{Bindings, Modifiers};
+use crate::keypad::KeyEvent;
 
 use std::error::Error;
+use std::time::{Duration, Instant};
 
 use core::fmt;
 
-use sdl2::{Sdl, EventPump};
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::{Sdl, EventPump, TimerSubsystem};
+use sdl2::event::{Event, WindowEvent};
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::mouse::MouseButton;
 
-/// The keyboard component, handling the keystrokes
+/// Id of the mouse events SDL synthesizes from touch events, which are handled as touch events
+const TOUCH_MOUSE_ID: u32 = u32::MAX;
+
+/// The keyboard component, handling the keystrokes, the mouse and the touch events
 pub struct SdlKeyboard {
     /// Event pump handling all the keyboard events
     pub event_pump: EventPump,
+    /// Timer the events are timestamped with
+    timer: TimerSubsystem,
+    /// Hotkeys of the emulator commands
+    bindings: Bindings,
+    /// Size of the window, in pixels
+    window_size: (f32, f32),
+    /// Position of the mouse while its left button is held, in window coordinates from 0.0 to 1.0
+    mouse: Option<(f32, f32)>,
+    /// Id and position of the fingers touching the window, in window coordinates from 0.0 to 1.0
+    fingers: Vec<(i64, (f32, f32))>,
 }
 
 impl SdlKeyboard {
-    /// Creates a new `SdlKeyboard` object
-    pub fn new(sdl: &Sdl) -> Result<Self, Box<dyn Error>> {
+    /// Creates a new `SdlKeyboard` object for a window of `window_width` x `window_height` pixels
+    pub fn new(sdl: &Sdl, window_width: usize, window_height: usize, bindings: Bindings) -> Result<Self, Box<dyn Error>> {
         Ok(SdlKeyboard {
             event_pump: sdl.event_pump()?,
+            timer: sdl.timer()?,
+            bindings: bindings,
+            window_size: (window_width as f32, window_height as f32),
+            mouse: None,
+            fingers: Vec::new(),
         })
     }
 
+    /// Converts the timestamp of an event, in milliseconds since SDL was initialized, to an `Instant`
+    fn event_time(&mut self, timestamp: u32) -> Instant {
+        let age = Duration::from_millis(self.timer.ticks().saturating_sub(timestamp) as u64);
+        let now = Instant::now();
+        now.checked_sub(age).unwrap_or(now)
+    }
+
+    /// Converts a position in window pixels to window coordinates from 0.0 to 1.0
+    fn normalize(&self, x: i32, y: i32) -> (f32, f32) {
+        (x as f32 / self.window_size.0, y as f32 / self.window_size.1)
+    }
+
     /// Converts a SDL keycode to a CHIP-8 keycode
     fn convert_keycode(key: Keycode) -> u8 {
         match key {
@@ -48,37 +82,81 @@ impl SdlKeyboard {
             _ => 0xFF,
         }
     }
+
+    /// Returns the modifiers held in `keymod`
+    fn convert_modifiers(keymod: Mod) -> Modifiers {
+        Modifiers {
+            ctrl: keymod.intersects(Mod::LCTRLMOD | Mod::RCTRLMOD),
+            shift: keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD),
+            alt: keymod.intersects(Mod::LALTMOD | Mod::RALTMOD),
+        }
+    }
 }
 
 impl KeyboardTrait for SdlKeyboard {
-    fn update_keys_state(&mut self, keys_state: &mut [bool]) -> bool {
-        let events: Vec<Event> = self.event_pump.poll_iter().collect();
+    fn poll_events(&mut self, events: &mut Vec<KeyEvent>) -> Vec<Command> {
+        let sdl_events: Vec<Event> = self.event_pump.poll_iter().collect();
+        let mut commands = Vec::new();
 
-        for event in events {
+        for event in sdl_events {
             match event {
                 Event::Quit {..} => {
-                    return false;
+                    commands.push(Command::Close);
+                },
+                Event::Window { win_event: WindowEvent::FocusLost, .. } |
+                Event::Window { win_event: WindowEvent::Minimized, .. } => {
+                    commands.push(Command::FocusLost);
+                },
+                Event::Window { win_event: WindowEvent::FocusGained, .. } |
+                Event::Window { win_event: WindowEvent::Restored, .. } => {
+                    commands.push(Command::FocusGained);
                 },
-                Event::KeyDown { keycode, .. } => {
+                Event::DropFile { filename, .. } => {
+                    commands.push(Command::LoadRom(filename));
+                },
+                Event::MouseButtonDown { which, mouse_btn: MouseButton::Left, x, y, .. } if which != TOUCH_MOUSE_ID => {
+                    self.mouse = Some(self.normalize(x, y));
+                },
+                Event::MouseMotion { which, x, y, .. } if which != TOUCH_MOUSE_ID && self.mouse.is_some() => {
+                    self.mouse = Some(self.normalize(x, y));
+                },
+                Event::MouseButtonUp { which, mouse_btn: MouseButton::Left, .. } if which != TOUCH_MOUSE_ID => {
+                    self.mouse = None;
+                },
+                Event::FingerDown { finger_id, x, y, .. } | Event::FingerMotion { finger_id, x, y, .. } => {
+                    self.fingers.retain(|(id, _)| *id != finger_id);
+                    self.fingers.push((finger_id, (x, y)));
+                },
+                Event::FingerUp { finger_id, .. } => {
+                    self.fingers.retain(|(id, _)| *id != finger_id);
+                },
+                Event::KeyDown { keycode, keymod, repeat, timestamp, .. } => {
                     match keycode {
                         Some(k) => {
-                            if let Keycode::Escape = k {
-                                return false;
+                            if let Some(command) = self.bindings.command(&k.name(), Self::convert_modifiers(keymod)) {
+                                if !repeat {
+                                    commands.push(command);
+                                }
+                                continue;
                             }
                             let k = Self::convert_keycode(k);
-                            if k != 0xFF {
-                                keys_state[k as usize] = true;
+                            if k != 0xFF && !repeat {
+                                events.push(KeyEvent { key: k, pressed: true, time: self.event_time(timestamp) });
                             }
                         }
                         _ => {},
                     }
                 },
-                Event::KeyUp { keycode, .. } => {
+                Event::KeyUp { keycode, timestamp, .. } => {
                     match keycode {
                         Some(k) => {
+                            if let Some(command) = self.bindings.release_command(&k.name()) {
+                                commands.push(command);
+                                continue;
+                            }
                             let k = Self::convert_keycode(k);
                             if k != 0xFF {
-                                keys_state[k as usize] = false;
+                                events.push(KeyEvent { key: k, pressed: false, time: self.event_time(timestamp) });
                             }
                         }
                         _ => {},
@@ -88,7 +166,13 @@ impl KeyboardTrait for SdlKeyboard {
             }
         }
 
-        true
+        commands
+    }
+
+    fn pointers(&self) -> Vec<(f32, f32)> {
+        self.mouse.iter().cloned()
+            .chain(self.fingers.iter().map(|(_, position)| *position))
+            .collect()
     }
 }
 
@@ -1,28 +1,24 @@
 use crate::handler::sound_trait::SoundTrait;
+use crate::handler::tone::ToneConfig;
+use crate::handler::beep_renderer::BeepRenderer;
 
 use std::error::Error;
+use std::time::Duration;
 
 use sdl2::audio::{AudioCallback, AudioSpecDesired, AudioDevice};
 use sdl2::{Sdl};
 
 use core::fmt;
 
-// https://docs.rs/sdl2/0.34.2/sdl2/audio/index.html
-struct SquareWave {
-    phase_inc: f32,
-    phase: f32,
-    volume: f32
-}
-
 /// Sound component for SDL
 pub struct SdlSound {
     /// Audio device, on which the sound will be played
-    audio_device: AudioDevice<SquareWave>,
+    audio_device: AudioDevice<BeepRenderer>,
 }
 
 impl SdlSound {
     /// Creates a new `SdlSound` object
-    pub fn new(sdl: &Sdl) -> Result<Self, Box<dyn Error>> {
+    pub fn new(sdl: &Sdl, tone_config: ToneConfig) -> Result<Self, Box<dyn Error>> {
         let audio_subsystem = sdl.audio()?;
 
         let desired_spec = AudioSpecDesired {
@@ -33,12 +29,10 @@ impl SdlSound {
 
         let device = audio_subsystem.open_playback(None, &desired_spec, |spec| {
             // Initialize the audio callback
-            SquareWave {
-                phase_inc: 440.0 / spec.freq as f32,
-                phase: 0.0,
-                volume: 0.25
-            }
+            BeepRenderer::new(tone_config, spec.freq as u32)
         })?;
+        // The device always runs, the renderer outputs silence when the beep is stopped
+        device.resume();
 
         Ok(SdlSound {
             audio_device: device,
@@ -47,28 +41,29 @@ impl SdlSound {
 }
 
 impl SoundTrait for SdlSound {
-    fn play_beep(&self) {
-        self.audio_device.resume();
+    fn play_beep(&mut self, timestamp: Duration) {
+        self.audio_device.lock().push_edge(true, timestamp);
+    }
+
+    fn stop_beep(&mut self, timestamp: Duration) {
+        self.audio_device.lock().push_edge(false, timestamp);
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.audio_device.lock().set_volume(volume);
     }
 
-    fn stop_beep(&self) {
-        self.audio_device.pause();
+    fn set_muted(&mut self, muted: bool) {
+        self.audio_device.lock().set_muted(muted);
     }
 }
 
-impl AudioCallback for SquareWave {
+// https://docs.rs/sdl2/0.34.2/sdl2/audio/index.html
+impl AudioCallback for BeepRenderer {
     type Channel = f32;
 
     fn callback(&mut self, out: &mut [f32]) {
-        // Generate a square wave
-        for x in out.iter_mut() {
-            *x = if self.phase <= 0.5 {
-                self.volume
-            } else {
-                -self.volume
-            };
-            self.phase = (self.phase + self.phase_inc) % 1.0;
-        }
+        self.render(out);
     }
 }
 
@@ -1,9 +1,16 @@
 use std::fmt::Debug;
+use std::time::Duration;
 
 /// Sound functions that a handler must implement
+///
+/// The beeps are timestamped in emulated time, so they can be rendered at the exact sample offsets
 pub trait SoundTrait: Debug {
-    /// Plays a beep sound
-    fn play_beep(&self);
-    /// Stops the beep sound
-    fn stop_beep(&self);
+    /// Plays a beep sound, starting at the emulated time `timestamp`
+    fn play_beep(&mut self, timestamp: Duration);
+    /// Stops the beep sound at the emulated time `timestamp`
+    fn stop_beep(&mut self, timestamp: Duration);
+    /// Sets the volume of the beep (0.0 - 1.0)
+    fn set_volume(&mut self, volume: f32);
+    /// Mutes or unmutes the sound
+    fn set_muted(&mut self, muted: bool);
 }
\ No newline at end of file
@@ -0,0 +1,147 @@
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug)]
+/// Enum containing the different waveforms the beep can use
+pub enum Waveform {
+    /// Square wave, the classic beep
+    SQUARE,
+    /// Pulse wave, a square wave high during `duty` of the period (0.0 - 1.0)
+    PULSE { duty: f32 },
+    /// Triangle wave, softer than the square wave
+    TRIANGLE,
+    /// Sine wave, the softest waveform
+    SINE,
+    /// White noise, changing value at the tone frequency
+    NOISE,
+}
+
+#[derive(Clone, Debug)]
+/// Settings of the beep
+pub struct ToneConfig {
+    /// Frequency of the beep, in Hz
+    pub frequency: f32,
+    /// Waveform of the beep
+    pub waveform: Waveform,
+    /// Volume of the beep (0.0 - 1.0)
+    pub volume: f32,
+    /// Time taken by the beep to reach its volume when it starts, in seconds
+    pub attack: f32,
+    /// Time taken by the beep to fade out when it stops, in seconds
+    pub release: f32,
+}
+
+impl Default for ToneConfig {
+    fn default() -> Self {
+        ToneConfig {
+            frequency: 440.0,
+            waveform: Waveform::SQUARE,
+            volume: 0.25,
+            attack: 0.005,
+            release: 0.005,
+        }
+    }
+}
+
+#[derive(Debug)]
+/// Tone generator producing the CHIP-8 beep, shared by the sound components
+///
+/// The generator always runs: when the beep is stopped, it fades out and outputs silence.
+/// Toggling the beep through the envelope instead of pausing the audio device avoids clicks
+pub struct Tone {
+    /// Settings of the beep
+    config: ToneConfig,
+    /// Number of samples per second of the output
+    sample_rate: f32,
+    /// Current phase of the wave (0.0 - 1.0)
+    phase: f32,
+    /// Current gain of the envelope (0.0 - 1.0)
+    envelope: f32,
+    /// Whether the beep is playing (the envelope goes up) or stopped (the envelope goes down)
+    gate: bool,
+    /// Whether the output is muted
+    muted: bool,
+    /// State of the pseudo-random generator used for the noise
+    noise_state: u32,
+    /// Current value of the noise
+    noise_value: f32,
+}
+
+impl Tone {
+    /// Creates a new `Tone` object for an output of `sample_rate` samples per second
+    pub fn new(config: ToneConfig, sample_rate: u32) -> Self {
+        Tone {
+            config: config,
+            sample_rate: sample_rate as f32,
+            phase: 0.0,
+            envelope: 0.0,
+            gate: false,
+            muted: false,
+            noise_state: 0x1234_5678,
+            noise_value: 0.0,
+        }
+    }
+
+    /// Starts (`true`) or stops (`false`) the beep
+    pub fn set_gate(&mut self, gate: bool) {
+        self.gate = gate;
+    }
+
+    /// Sets the volume of the beep (0.0 - 1.0)
+    pub fn set_volume(&mut self, volume: f32) {
+        self.config.volume = volume;
+    }
+
+    /// Mutes or unmutes the output
+    pub fn set_muted(&mut self, muted: bool) {
+        self.muted = muted;
+    }
+
+    /// Generates the next sample
+    pub fn next_sample(&mut self) -> f32 {
+        self.update_envelope();
+        if self.envelope <= 0.0 {
+            // We restart the wave from the beginning for the next beep
+            self.phase = 0.0;
+            return 0.0;
+        }
+
+        let value = match self.config.waveform {
+            Waveform::SQUARE => if self.phase < 0.5 { 1.0 } else { -1.0 },
+            Waveform::PULSE { duty } => if self.phase < duty { 1.0 } else { -1.0 },
+            Waveform::TRIANGLE => 1.0 - 4.0 * (self.phase - 0.5).abs(),
+            Waveform::SINE => (2.0 * PI * self.phase).sin(),
+            Waveform::NOISE => self.noise_value,
+        };
+
+        self.phase += self.config.frequency / self.sample_rate;
+        if self.phase >= 1.0 {
+            self.phase %= 1.0;
+            self.noise_value = self.next_noise();
+        }
+
+        if self.muted {
+            return 0.0;
+        }
+        value * self.config.volume * self.envelope
+    }
+
+    /// Moves the envelope towards 1.0 if the beep is playing, towards 0.0 otherwise
+    fn update_envelope(&mut self) {
+        if self.gate {
+            let step = 1.0 / (self.config.attack * self.sample_rate).max(1.0);
+            self.envelope = (self.envelope + step).min(1.0);
+        }
+        else {
+            let step = 1.0 / (self.config.release * self.sample_rate).max(1.0);
+            self.envelope = (self.envelope - step).max(0.0);
+        }
+    }
+
+    /// Returns a pseudo-random value between -1.0 and 1.0 (xorshift32)
+    fn next_noise(&mut self) -> f32 {
+        self.noise_state ^= self.noise_state << 13;
+        self.noise_state ^= self.noise_state >> 17;
+        self.noise_state ^= self.noise_state << 5;
+        (self.noise_state as f32 / u32::MAX as f32) * 2.0 - 1.0
+    }
+}
@@ -0,0 +1,132 @@
+use crate::handler::sound_trait::SoundTrait;
+use crate::handler::tone::ToneConfig;
+use crate::handler::beep_renderer::BeepRenderer;
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{BufWriter, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+/// Sample rate of the recordings
+const SAMPLE_RATE: u32 = 44100;
+/// Size of the WAV header, in bytes
+const HEADER_SIZE: u32 = 44;
+/// Maximum number of samples of a recording, the sizes of the WAV header being 32-bit (about 13.5 hours)
+const MAX_SAMPLES: u64 = (u32::MAX - (HEADER_SIZE - 8)) as u64 / 2;
+
+#[derive(Debug)]
+/// Records the beep into a 16-bit mono PCM WAV file
+///
+/// The beep is rendered offline with the same renderer and settings as the sound component,
+/// from the same edges, but synchronized with the emulated time instead of an audio device
+pub struct WavRecorder {
+    /// Path of the WAV file
+    path: String,
+    /// Output file, the header sizes are written when the recording is finished
+    writer: BufWriter<File>,
+    /// Beep renderer
+    renderer: BeepRenderer,
+    /// Samples rendered but not written yet
+    samples: Vec<f32>,
+    /// Number of samples written
+    samples_written: u64,
+}
+
+impl WavRecorder {
+    /// Creates a new `WavRecorder` object, starting the recording at the emulated time `time`
+    pub fn new(path: &str, tone_config: ToneConfig, volume: f32, muted: bool, time: Duration) -> Result<Self, Box<dyn Error>> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        WavRecorder::write_header(&mut writer, 0)?;
+
+        let mut renderer = BeepRenderer::new(tone_config, SAMPLE_RATE);
+        renderer.set_volume(volume);
+        renderer.set_muted(muted);
+        // Sets the clock of the renderer
+        renderer.render_until(time, &mut Vec::new());
+
+        Ok(WavRecorder {
+            path: path.to_string(),
+            writer: writer,
+            renderer: renderer,
+            samples: Vec::new(),
+            samples_written: 0,
+        })
+    }
+
+    /// Path of the WAV file
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Renders and writes the samples up to the emulated time `time`
+    pub fn advance(&mut self, time: Duration) -> Result<(), Box<dyn Error>> {
+        self.renderer.render_until(time, &mut self.samples);
+        // The samples past the size limit of the WAV format are dropped
+        let room = MAX_SAMPLES - self.samples_written;
+        self.samples.truncate(room.min(self.samples.len() as u64) as usize);
+        self.samples_written += self.samples.len() as u64;
+        for sample in self.samples.drain(..) {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.writer.write_all(&value.to_le_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Whether the recording reached the size limit of the WAV format, and must be finished
+    pub fn is_full(&self) -> bool {
+        self.samples_written >= MAX_SAMPLES
+    }
+
+    /// Goes on recording from the emulated time `time`, which can be earlier than the current one
+    /// (e.g. when a saved state is loaded), without any gap in the recording
+    pub fn seek(&mut self, time: Duration) {
+        self.renderer.seek(time);
+    }
+
+    /// Writes the WAV header for `data_size` bytes of samples
+    fn write_header<W: Write>(writer: &mut W, data_size: u32) -> Result<(), Box<dyn Error>> {
+        writer.write_all(b"RIFF")?;
+        writer.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+        writer.write_all(b"WAVE")?;
+        writer.write_all(b"fmt ")?;
+        // Size of the format chunk, PCM format, mono
+        writer.write_all(&16u32.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&1u16.to_le_bytes())?;
+        writer.write_all(&SAMPLE_RATE.to_le_bytes())?;
+        // Byte rate, block align and bits per sample
+        writer.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+        writer.write_all(&2u16.to_le_bytes())?;
+        writer.write_all(&16u16.to_le_bytes())?;
+        writer.write_all(b"data")?;
+        writer.write_all(&data_size.to_le_bytes())?;
+        Ok(())
+    }
+
+    /// Renders the samples up to the emulated time `time`, then writes the final sizes in the header
+    pub fn finish(mut self, time: Duration) -> Result<(), Box<dyn Error>> {
+        self.advance(time)?;
+        self.writer.seek(SeekFrom::Start(0))?;
+        WavRecorder::write_header(&mut self.writer, (self.samples_written * 2) as u32)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+impl SoundTrait for WavRecorder {
+    fn play_beep(&mut self, timestamp: Duration) {
+        self.renderer.push_edge(true, timestamp);
+    }
+
+    fn stop_beep(&mut self, timestamp: Duration) {
+        self.renderer.push_edge(false, timestamp);
+    }
+
+    fn set_volume(&mut self, volume: f32) {
+        self.renderer.set_volume(volume);
+    }
+
+    fn set_muted(&mut self, muted: bool) {
+        self.renderer.set_muted(muted);
+    }
+}
@@ -0,0 +1,128 @@
+use crate::keypad::{Keypad, KEYBOARD_SIZE};
+use crate::chip8::FRAME_RATE;
+
+use std::error::Error;
+
+/// Rate of an autofire key given without a rate, in presses per second
+const DEFAULT_AUTOFIRE_RATE: f64 = 10.0;
+
+#[derive(Clone, Debug, Default)]
+/// Keys pressed and released automatically while they are held, at a rate in presses per second
+pub struct Autofire {
+    /// Rate of each CHIP-8 key, `None` if the key has no autofire
+    pub rates: [Option<f64>; KEYBOARD_SIZE],
+}
+
+impl Autofire {
+    /// Parses a comma-separated list of `key[:rate]`, e.g. `5:10,A`, the keys being CHIP-8 keys in hexadecimal
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut autofire = Autofire::default();
+        for entry in s.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let mut parts = entry.split(':');
+            let key = parts.next().unwrap_or("").trim();
+            let key = u8::from_str_radix(key, 16)
+                .ok()
+                .filter(|&k| (k as usize) < KEYBOARD_SIZE)
+                .ok_or(format!("Invalid autofire key: {}", key))?;
+            let rate = match parts.next() {
+                Some(rate) => rate.trim().parse::<f64>()
+                    .ok()
+                    .filter(|&r| r > 0.0 && r <= FRAME_RATE / 2.0)
+                    .ok_or(format!("Invalid autofire rate: {}", rate))?,
+                None => DEFAULT_AUTOFIRE_RATE,
+            };
+            autofire.rates[key as usize] = Some(rate);
+        }
+        Ok(autofire)
+    }
+}
+
+#[derive(Debug)]
+/// Layer between the held keys and the keys seen by the ROM, applied at the start of each frame.
+/// It presses and releases the autofire keys while they are held, and records and plays back an input macro
+pub struct InputLayer {
+    /// Autofire settings
+    autofire: Autofire,
+    /// Number of frames each key has been held for
+    held_frames: [u64; KEYBOARD_SIZE],
+    /// Keys state of each frame of the macro being recorded, `None` when not recording
+    recording: Option<Vec<[bool; KEYBOARD_SIZE]>>,
+    /// Keys state of each frame of the recorded macro
+    recorded: Vec<[bool; KEYBOARD_SIZE]>,
+    /// Next frame of the macro to play, `None` when not playing
+    playback: Option<usize>,
+}
+
+impl InputLayer {
+    /// Creates a new `InputLayer` object
+    pub fn new(autofire: Autofire) -> Self {
+        InputLayer {
+            autofire: autofire,
+            held_frames: [0; KEYBOARD_SIZE],
+            recording: None,
+            recorded: Vec::new(),
+            playback: None,
+        }
+    }
+
+    /// Computes the keys state seen by the ROM from the held keys of `keypad`, called at the start of each frame
+    pub fn apply(&mut self, keypad: &mut Keypad) {
+        for key in 0..KEYBOARD_SIZE {
+            self.held_frames[key] = if keypad.held[key] { self.held_frames[key] + 1 } else { 0 };
+
+            // The key is pressed for the first half of each period, starting as soon as it is held
+            if let Some(rate) = self.autofire.rates[key] {
+                keypad.keys_state[key] = keypad.held[key] && {
+                    let held_time = (self.held_frames[key] - 1) as f64 / FRAME_RATE;
+                    (held_time * rate).fract() < 0.5
+                };
+            }
+        }
+
+        // The recording gets the keys of the player, without the macro being played
+        if let Some(recording) = &mut self.recording {
+            recording.push(keypad.keys_state);
+        }
+
+        if let Some(frame) = self.playback {
+            match self.recorded.get(frame) {
+                Some(keys) => {
+                    for (state, &pressed) in keypad.keys_state.iter_mut().zip(keys.iter()) {
+                        *state |= pressed;
+                    }
+                    self.playback = Some(frame + 1);
+                },
+                None => self.playback = None,
+            }
+        }
+    }
+
+    /// Starts recording a macro, or stops the recording and keeps the macro. Returns whether it is now recording
+    pub fn toggle_recording(&mut self) -> bool {
+        match self.recording.take() {
+            Some(recording) => {
+                self.recorded = recording;
+                false
+            },
+            None => {
+                self.recording = Some(Vec::new());
+                true
+            },
+        }
+    }
+
+    /// Plays the recorded macro from its start, or stops it if it is playing.
+    /// Returns whether it is now playing, `false` if there is no macro
+    pub fn toggle_playback(&mut self) -> bool {
+        self.playback = match self.playback {
+            None if !self.recorded.is_empty() => Some(0),
+            _ => None,
+        };
+        self.playback.is_some()
+    }
+
+    /// Returns the length of the recorded macro, in frames
+    pub fn macro_frames(&self) -> usize {
+        self.recorded.len()
+    }
+}
@@ -1,13 +1,61 @@
-use std::error::Error;
+use crate::error::Chip8Error;
+
+use std::collections::VecDeque;
+use std::time::Instant;
 
 /// The max size of the keyboard (16 for CHIP-8)
 pub const KEYBOARD_SIZE: usize = 16;
+/// Number of frames a key stays marked as tested after the ROM tested it
+const TESTED_FRAMES: u8 = 30;
+/// Maximum number of queued key events, the oldest ones are applied right away beyond it (e.g. while paused)
+const MAX_EVENTS: usize = 64;
+
+#[derive(Clone, Copy, Debug)]
+/// A CHIP-8 key pressed or released
+pub struct KeyEvent {
+    /// CHIP-8 key (0x0 - 0xF)
+    pub key: u8,
+    /// `true` if the key was pressed, `false` if released
+    pub pressed: bool,
+    /// Time the event happened at, used to merge the events of the different inputs in order
+    pub time: Instant,
+}
+
+#[derive(Clone, Copy, Debug)]
+/// State of an `FX0A` wait, saved and restored with the state of the VM
+pub struct WaitState {
+    /// Key pressed during the wait, whose release ends it
+    awaited: Option<u8>,
+    /// Whether an `FX0A` instruction is waiting for a key
+    waiting: bool,
+}
 
 #[derive(Debug)]
 /// Struct containing the active key strokes
+///
+/// The key events are queued, then applied to the held keys at the start of each frame.
+/// A key changes at most once per frame, so a key pressed and released between two frames is still seen pressed
+/// by the ROM during a frame
 pub struct Keypad {
-    /// Array of the keys state. `true` if pressed, `false` if released.
+    /// Array of the keys state seen by the ROM. `true` if pressed, `false` if released.
+    /// The held keys, possibly changed by the input layer (autofire, macros)
     pub keys_state: [bool; KEYBOARD_SIZE],
+    /// Array of the keys held on the keyboard or the virtual keypad
+    pub held: [bool; KEYBOARD_SIZE],
+    /// Key events not applied yet, the oldest first
+    events: VecDeque<KeyEvent>,
+    /// Key pressed during an `FX0A` wait, the wait ends when it is released
+    awaited: Option<u8>,
+    /// Number of frames left during which each key is marked as recently tested by `EX9E` / `EXA1`
+    tested: [u8; KEYBOARD_SIZE],
+    /// Whether an `FX0A` instruction is waiting for a key
+    waiting: bool,
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        Keypad::new()
+    }
 }
 
 impl Keypad {
@@ -15,12 +63,121 @@ impl Keypad {
     pub fn new() -> Self {
         Keypad {
             keys_state: [false; KEYBOARD_SIZE],
+            held: [false; KEYBOARD_SIZE],
+            events: VecDeque::new(),
+            awaited: None,
+            tested: [0; KEYBOARD_SIZE],
+            waiting: false,
+        }
+    }
+
+    /// Queues `events`, in the order they happened. Events of keys that don't exist are dropped
+    pub fn push_events(&mut self, mut events: Vec<KeyEvent>) {
+        events.retain(|e| (e.key as usize) < KEYBOARD_SIZE);
+        events.sort_by_key(|e| e.time);
+        self.events.extend(events);
+        while self.events.len() > MAX_EVENTS {
+            if let Some(event) = self.events.pop_front() {
+                self.held[event.key as usize] = event.pressed;
+            }
+        }
+    }
+
+    /// Applies the queued events to the held keys, at most one change per key, then copies them to the keys state.
+    /// The events following a second change of a key are kept for the next frame, so the order is kept
+    pub fn apply_events(&mut self) {
+        let mut changed = [false; KEYBOARD_SIZE];
+        while let Some(event) = self.events.front() {
+            let key = event.key as usize;
+            if self.held[key] != event.pressed {
+                if changed[key] {
+                    break;
+                }
+                self.held[key] = event.pressed;
+                changed[key] = true;
+            }
+            self.events.pop_front();
+        }
+        self.keys_state = self.held;
+    }
+
+    /// Applies all the queued events, when only the final state matters (e.g. in the launcher)
+    pub fn apply_all_events(&mut self) {
+        for event in self.events.drain(..) {
+            self.held[event.key as usize] = event.pressed;
+        }
+        self.keys_state = self.held;
+    }
+
+    /// Waits for a key to be pressed then released, as `FX0A` does on the COSMAC VIP.
+    /// Called each time the instruction runs, returns the key once released
+    pub fn wait_key_release(&mut self) -> Option<u8> {
+        match self.awaited {
+            None => {
+                self.awaited = self.first_pressed_key();
+                None
+            },
+            Some(key) if !self.keys_state[key as usize] => {
+                self.awaited = None;
+                Some(key)
+            },
+            Some(_) => None,
+        }
+    }
+
+    /// Returns the key pressed during an `FX0A` wait, whose release ends the wait
+    pub fn awaited_key(&self) -> Option<u8> {
+        self.awaited
+    }
+
+    /// Returns the state of the `FX0A` wait, to be saved with the state of the VM
+    pub fn wait_state(&self) -> WaitState {
+        WaitState {
+            awaited: self.awaited,
+            waiting: self.waiting,
+        }
+    }
+
+    /// Restores the state of the `FX0A` wait saved with `wait_state`
+    pub fn restore_wait_state(&mut self, state: WaitState) {
+        self.awaited = state.awaited;
+        self.waiting = state.waiting;
+    }
+
+    /// Returns `true` if `key_code` is pressed, and marks the key as recently tested by the ROM
+    pub fn test_key(&mut self, key_code: u8) -> Result<bool, Chip8Error> {
+        let pressed = *self.is_key_pressed(key_code)?;
+        self.tested[key_code as usize] = TESTED_FRAMES;
+        Ok(pressed)
+    }
+
+    /// Returns how recently `key_code` was tested by the ROM, from 1.0 (this frame) to 0.0 (not recently)
+    pub fn tested(&self, key_code: u8) -> f32 {
+        self.tested.get(key_code as usize).map_or(0.0, |&tested| tested as f32 / TESTED_FRAMES as f32)
+    }
+
+    /// Sets whether an `FX0A` instruction is waiting for a key
+    pub fn set_waiting(&mut self, waiting: bool) {
+        self.waiting = waiting;
+    }
+
+    /// Returns whether an `FX0A` instruction is waiting for a key
+    pub fn is_waiting(&self) -> bool {
+        self.waiting
+    }
+
+    /// Ages the recently tested keys, called at the end of each frame
+    pub fn update_tested(&mut self) {
+        for tested in self.tested.iter_mut() {
+            *tested = tested.saturating_sub(1);
         }
     }
 
     /// Returns `true` if `key_code` corresponds to `key_pressed`, `false` otherwise
-    pub fn is_key_pressed(&self, key_code: u8) -> Result<&bool, Box<dyn Error>> {
-        self.keys_state.get(key_code as usize).ok_or("Invalid key code".into())
+    pub fn is_key_pressed(&self, key_code: u8) -> Result<&bool, Chip8Error> {
+        self.keys_state.get(key_code as usize).ok_or(
+            Chip8Error::InvalidKey { pc: None, opcode: None, key: key_code }
+        )
     }
 
     /// Returns the first pressed key we encounter, `None` otherwise
@@ -32,4 +189,113 @@ impl Keypad {
         }
         None
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cpu::{self, Cpu, PROGRAM_START};
+    use crate::quirks::Quirks;
+    use crate::ram::Ram;
+    use crate::screen::Screen;
+
+    use std::time::Duration;
+
+    /// Builds the events of `key` for each state of `changes`, in order
+    fn events(key: u8, changes: &[bool]) -> Vec<KeyEvent> {
+        let start = Instant::now();
+        changes.iter().enumerate()
+            .map(|(i, &pressed)| KeyEvent {
+                key: key,
+                pressed: pressed,
+                time: start + Duration::from_millis(i as u64),
+            })
+            .collect()
+    }
+
+    /// A VM running `F50A` (V5 = key) in a loop
+    struct KeyWait {
+        cpu: Cpu,
+        ram: Ram,
+        screen: Screen,
+        keypad: Keypad,
+    }
+
+    impl KeyWait {
+        fn new(quirks: Quirks) -> Self {
+            let mut ram = Ram::new();
+            ram.write_bytes(PROGRAM_START as usize, &[0xF5, 0x0A]).unwrap();
+            KeyWait {
+                cpu: Cpu::new(quirks, cpu::new_rng(Some(0))),
+                ram: ram,
+                screen: Screen::new(),
+                keypad: Keypad::new(),
+            }
+        }
+
+        /// Runs a frame of one instruction after queuing `events`, returns the key if `F50A` completed
+        fn frame(&mut self, events: Vec<KeyEvent>) -> Option<u8> {
+            self.keypad.push_events(events);
+            self.keypad.apply_events();
+            self.cpu.run_instruction(&mut self.ram, &mut self.screen, &mut self.keypad, false).unwrap();
+            if self.cpu.pc() == PROGRAM_START + 2 {
+                Some(self.cpu.read_reg_vx(5))
+            }
+            else {
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn press_and_release_in_one_frame_are_seen_over_two_frames() {
+        let mut keypad = Keypad::new();
+        keypad.push_events(events(0x3, &[true, false]));
+
+        keypad.apply_events();
+        assert!(keypad.keys_state[0x3]);
+        keypad.apply_events();
+        assert!(!keypad.keys_state[0x3]);
+    }
+
+    #[test]
+    fn key_wait_completes_on_release() {
+        let mut vm = KeyWait::new(Quirks::default());
+
+        assert_eq!(vm.frame(Vec::new()), None);
+        assert_eq!(vm.frame(events(0x7, &[true])), None);
+        assert_eq!(vm.keypad.awaited_key(), Some(0x7));
+        assert_eq!(vm.frame(Vec::new()), None);
+        assert!(vm.keypad.is_waiting());
+        assert_eq!(vm.frame(events(0x7, &[false])), Some(0x7));
+        assert!(!vm.keypad.is_waiting());
+    }
+
+    #[test]
+    fn key_wait_press_quirk_completes_on_press() {
+        let mut vm = KeyWait::new(Quirks { key_wait_press: true });
+
+        assert_eq!(vm.frame(Vec::new()), None);
+        assert_eq!(vm.frame(events(0x7, &[true])), Some(0x7));
+        assert!(!vm.keypad.is_waiting());
+    }
+
+    #[test]
+    fn queue_applies_the_oldest_events_beyond_its_cap() {
+        let mut keypad = Keypad::new();
+        let mut queued = events(0x2, &[true]);
+        let mut later = events(0x1, &[true; MAX_EVENTS]);
+        for event in later.iter_mut() {
+            event.time += Duration::from_secs(1);
+        }
+        queued.append(&mut later);
+        keypad.push_events(queued);
+
+        // The oldest event is applied to the held keys right away, the ROM sees it on the next frame
+        assert_eq!(keypad.events.len(), MAX_EVENTS);
+        assert!(keypad.held[0x2]);
+        assert!(!keypad.keys_state[0x2]);
+        keypad.apply_events();
+        assert!(keypad.keys_state[0x2]);
+        assert!(keypad.keys_state[0x1]);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::frame::Frame;
+use crate::keypad::Keypad;
+use crate::font::{self, GLYPH_WIDTH, GLYPH_HEIGHT};
+
+/// Keys of the CHIP-8 keypad, as laid out on the COSMAC VIP
+pub const KEYPAD_LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+/// Size of a key, in font pixels
+const KEY_SIZE: usize = 11;
+/// Space between two keys, in font pixels
+const KEY_SPACING: usize = 1;
+/// Background of the keypad
+const BACKGROUND_COLOR: u32 = 0x202020;
+/// Released key
+const KEY_COLOR: u32 = 0x505050;
+/// Pressed key
+const PRESSED_COLOR: u32 = 0xE0E0E0;
+/// Mark of a key recently tested by the ROM
+const TESTED_COLOR: u32 = 0x40C040;
+/// Border of the keypad while an `FX0A` instruction waits for a key, and key whose release it waits for
+const WAITING_COLOR: u32 = 0xE0A020;
+/// Label of a released key
+const LABEL_COLOR: u32 = 0xFFFFFF;
+/// Label of a pressed key
+const PRESSED_LABEL_COLOR: u32 = 0x000000;
+
+/// Returns the width and height of the keypad drawn at `scale`, in pixels
+pub fn keypad_size(scale: usize) -> usize {
+    (4 * (KEY_SIZE + KEY_SPACING) + KEY_SPACING) * scale
+}
+
+/// Returns the key at (`x`,`y`) relative to the top-left corner of the keypad drawn at `scale`,
+/// `None` if the position is outside of the keys
+pub fn key_at(x: usize, y: usize, scale: usize) -> Option<u8> {
+    let step = (KEY_SIZE + KEY_SPACING) * scale;
+    let (x, y) = (x.checked_sub(KEY_SPACING * scale)?, y.checked_sub(KEY_SPACING * scale)?);
+    let (col, row) = (x / step, y / step);
+    // The spacing between the keys doesn't belong to any key
+    if col >= 4 || row >= 4 || x % step >= KEY_SIZE * scale || y % step >= KEY_SIZE * scale {
+        return None;
+    }
+    Some(KEYPAD_LAYOUT[row][col])
+}
+
+/// Draws the 4x4 keypad with its top-left corner at (`x`,`y`):
+/// the pressed keys are lit, the keys recently tested by the ROM (`EX9E` / `EXA1`) are underlined,
+/// and the keypad gets a border while an `FX0A` instruction waits for a key, the pressed key it then waits
+/// the release of being drawn in the same color
+pub fn draw_keypad(frame: &mut Frame, x: usize, y: usize, keypad: &Keypad, scale: usize) {
+    let size = keypad_size(scale);
+    if keypad.is_waiting() {
+        frame.fill_rect(x, y, size, size, WAITING_COLOR);
+        frame.fill_rect(x + scale, y + scale, size - 2 * scale, size - 2 * scale, BACKGROUND_COLOR);
+    }
+    else {
+        frame.fill_rect(x, y, size, size, BACKGROUND_COLOR);
+    }
+
+    let key_size = KEY_SIZE * scale;
+    for (row, keys) in KEYPAD_LAYOUT.iter().enumerate() {
+        for (col, &key) in keys.iter().enumerate() {
+            let left = x + (KEY_SPACING + col * (KEY_SIZE + KEY_SPACING)) * scale;
+            let top = y + (KEY_SPACING + row * (KEY_SIZE + KEY_SPACING)) * scale;
+            let pressed = keypad.keys_state[key as usize];
+            let color = if keypad.awaited_key() == Some(key) {
+                WAITING_COLOR
+            }
+            else if pressed {
+                PRESSED_COLOR
+            }
+            else {
+                KEY_COLOR
+            };
+            frame.fill_rect(left, top, key_size, key_size, color);
+
+            // The mark fades out as the test gets older
+            let tested = keypad.tested(key);
+            if tested > 0.0 {
+                let width = ((key_size as f32 * tested).round() as usize).max(scale);
+                frame.fill_rect(left, top + key_size - scale, width, scale, TESTED_COLOR);
+            }
+
+            let label = format!("{:X}", key);
+            let label_x = left + (KEY_SIZE - GLYPH_WIDTH) / 2 * scale;
+            let label_y = top + (KEY_SIZE - GLYPH_HEIGHT) / 2 * scale;
+            let color = if pressed { PRESSED_LABEL_COLOR } else { LABEL_COLOR };
+            font::draw_text(frame, label_x, label_y, &label, scale, color);
+        }
+    }
+}
@@ -0,0 +1,376 @@
+use crate::cpu::{self, Cpu, PROGRAM_START};
+use crate::ram::Ram;
+use crate::screen::{Screen, WIDTH, HEIGHT};
+use crate::keypad::{Keypad, KEYBOARD_SIZE};
+use crate::quirks::Quirks;
+use crate::config::ConfigFile;
+use crate::handler::Command;
+use crate::frame::Frame;
+use crate::scaler::{PIXEL_ON, PIXEL_OFF};
+use crate::font::{self, CHAR_WIDTH, LINE_HEIGHT};
+use crate::utils::log_warning;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Extensions of the files listed as ROMs
+const ROM_EXTENSIONS: [&str; 5] = ["ch8", "c8", "rom", "sc8", "xo8"];
+/// Metadata file of a ROM directory, an INI file with a `[ROM]` section per ROM giving its `title` and `platform`
+const METADATA_FILE: &str = "roms.ini";
+/// File keeping the recent ROMs, one path per line, the most recent first
+const RECENT_FILE: &str = "yac8e.recent";
+/// Maximum number of recent ROMs kept
+const MAX_RECENT: usize = 8;
+/// Color of the text
+const TEXT_COLOR: u32 = 0xFFFFFF;
+/// Color of the secondary text (platform, help)
+const DIM_COLOR: u32 = 0x909090;
+/// Color of the selected entry background
+const SELECTION_COLOR: u32 = 0x3050A0;
+/// Color of the preview border
+const BORDER_COLOR: u32 = 0x606060;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Kind of an entry of the launcher list
+enum EntryKind {
+    /// A recently launched ROM
+    Recent,
+    /// The parent directory
+    Parent,
+    /// A subdirectory
+    Directory,
+    /// A ROM of the current directory
+    Rom,
+}
+
+#[derive(Clone, Debug)]
+/// An entry of the launcher list
+struct Entry {
+    /// Kind of the entry
+    kind: EntryKind,
+    /// Path of the ROM or directory
+    path: PathBuf,
+    /// Title from the metadata, or file name
+    title: String,
+    /// Platform from the metadata, or guessed from the extension. Empty for directories
+    platform: String,
+}
+
+impl Entry {
+    /// Creates an entry for the ROM at `path`, reading its metadata from the `roms.ini` file of its directory if any
+    fn rom(kind: EntryKind, path: PathBuf) -> Self {
+        let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let metadata = path.parent()
+            .map(|dir| dir.join(METADATA_FILE))
+            .filter(|file| file.exists())
+            .and_then(|file| ConfigFile::load(&file, &file_name).ok())
+            .unwrap_or_default();
+
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+        let platform = match extension.as_str() {
+            "sc8" => "SUPER-CHIP",
+            "xo8" => "XO-CHIP",
+            _ => "CHIP-8",
+        };
+
+        Entry {
+            kind: kind,
+            title: metadata.get("title").map(String::from).unwrap_or_else(|| file_name.clone()),
+            platform: metadata.get("platform").unwrap_or(platform).to_string(),
+            path: path,
+        }
+    }
+
+    /// Creates an entry for the directory at `path`, `..` if it is the parent directory
+    fn directory(kind: EntryKind, path: PathBuf) -> Self {
+        let title = match kind {
+            EntryKind::Parent => "[..]".to_string(),
+            _ => format!("[{}]", path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default()),
+        };
+        Entry {
+            kind: kind,
+            path: path,
+            title: title,
+            platform: String::new(),
+        }
+    }
+
+    /// Label of the entry in the list
+    fn label(&self) -> String {
+        match self.kind {
+            EntryKind::Recent => format!("* {}", self.title),
+            _ => self.title.clone(),
+        }
+    }
+}
+
+#[derive(Debug)]
+/// A VM running the selected ROM in the background, to show a live preview
+struct Preview {
+    cpu: Cpu,
+    ram: Ram,
+    screen: Screen,
+    keypad: Keypad,
+    /// Whether the ROM still runs, it stops on the first error
+    running: bool,
+}
+
+impl Preview {
+    /// Creates a new `Preview` object running the ROM at `path`, `None` if it can't be loaded
+    fn new(path: &Path) -> Option<Self> {
+        let data = fs::read(path).ok()?;
+        let mut ram = Ram::new();
+        ram.write_bytes(PROGRAM_START as usize, &data).ok()?;
+
+        Some(Preview {
+            cpu: Cpu::new(Quirks::default(), cpu::new_rng(None)),
+            ram: ram,
+            screen: Screen::new(),
+            keypad: Keypad::new(),
+            running: true,
+        })
+    }
+
+    /// Runs `frames` frames of `instructions_per_frame` instructions
+    fn run(&mut self, frames: u32, instructions_per_frame: u32) {
+        for _ in 0..frames {
+            for _ in 0..instructions_per_frame {
+                if !self.running {
+                    return;
+                }
+                self.running = self.cpu.run_instruction(&mut self.ram, &mut self.screen, &mut self.keypad, false).is_ok();
+            }
+            self.cpu.update_timers(false);
+        }
+    }
+}
+
+#[derive(Debug)]
+/// In-window menu to browse a ROM directory and launch a ROM, shown when no ROM is given
+///
+/// Navigation works with the arrow keys, Enter and Backspace, or with the CHIP-8 keypad:
+/// 2 / 8 to move, 5 / 6 to launch and 4 to go back
+pub struct Launcher {
+    /// Directory currently browsed
+    dir: PathBuf,
+    /// Recent ROMs, the most recent first
+    recent: Vec<PathBuf>,
+    /// Entries of the list: the recent ROMs, then the parent directory, the subdirectories and the ROMs
+    entries: Vec<Entry>,
+    /// Index of the selected entry
+    selected: usize,
+    /// Live preview of the selected ROM
+    preview: Option<Preview>,
+    /// Number of instructions per frame of the preview
+    instructions_per_frame: u32,
+    /// Keypad state at the last update, to detect the key presses
+    prev_keys: [bool; KEYBOARD_SIZE],
+}
+
+impl Launcher {
+    /// Creates a new `Launcher` object browsing `dir`
+    pub fn new(dir: &str, instructions_per_frame: u32) -> Self {
+        let dir = fs::canonicalize(dir).unwrap_or_else(|_| PathBuf::from(dir));
+        let recent = fs::read_to_string(RECENT_FILE)
+            .map(|content| content.lines().map(PathBuf::from).filter(|p| p.exists()).collect())
+            .unwrap_or_default();
+
+        let mut launcher = Launcher {
+            dir: dir,
+            recent: recent,
+            entries: Vec::new(),
+            selected: 0,
+            preview: None,
+            instructions_per_frame: instructions_per_frame,
+            prev_keys: [false; KEYBOARD_SIZE],
+        };
+        launcher.refresh();
+        launcher
+    }
+
+    /// Adds `path` at the top of the recent ROMs, and saves them
+    pub fn add_recent(&mut self, path: &str) {
+        let path = fs::canonicalize(path).unwrap_or_else(|_| PathBuf::from(path));
+        self.recent.retain(|p| *p != path);
+        self.recent.insert(0, path);
+        self.recent.truncate(MAX_RECENT);
+
+        let content: Vec<String> = self.recent.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+        if let Err(e) = fs::write(RECENT_FILE, content.join("\n")) {
+            log_warning(format!("Unable to save the recent ROMs: {}", e));
+        }
+        self.refresh();
+    }
+
+    /// Lists the entries of the current directory
+    fn refresh(&mut self) {
+        let mut entries: Vec<Entry> = self.recent.iter()
+            .map(|p| Entry::rom(EntryKind::Recent, p.clone()))
+            .collect();
+
+        if let Some(parent) = self.dir.parent() {
+            entries.push(Entry::directory(EntryKind::Parent, parent.to_path_buf()));
+        }
+
+        let mut dirs = Vec::new();
+        let mut roms = Vec::new();
+        match fs::read_dir(&self.dir) {
+            Ok(read_dir) => {
+                for path in read_dir.filter_map(|e| e.ok()).map(|e| e.path()) {
+                    let hidden = path.file_name().map(|n| n.to_string_lossy().starts_with('.')).unwrap_or(true);
+                    if hidden {
+                        continue;
+                    }
+                    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase()).unwrap_or_default();
+                    if path.is_dir() {
+                        dirs.push(path);
+                    }
+                    else if ROM_EXTENSIONS.contains(&extension.as_str()) {
+                        roms.push(path);
+                    }
+                }
+            },
+            Err(e) => log_warning(format!("Unable to read the directory {}: {}", self.dir.display(), e)),
+        }
+        dirs.sort_by_key(|p| p.to_string_lossy().to_lowercase());
+        roms.sort_by_key(|p| p.to_string_lossy().to_lowercase());
+        entries.extend(dirs.into_iter().map(|p| Entry::directory(EntryKind::Directory, p)));
+        entries.extend(roms.into_iter().map(|p| Entry::rom(EntryKind::Rom, p)));
+
+        self.entries = entries;
+        self.select(0);
+    }
+
+    /// Selects the entry at `index`, clamped to the list, and starts its preview
+    fn select(&mut self, index: usize) {
+        self.selected = index.min(self.entries.len().saturating_sub(1));
+        self.preview = match self.entries.get(self.selected) {
+            Some(entry) if entry.kind == EntryKind::Recent || entry.kind == EntryKind::Rom => Preview::new(&entry.path),
+            _ => None,
+        };
+    }
+
+    /// Opens the selected entry. Returns the path of the ROM to launch if it is a ROM
+    fn open_selected(&mut self) -> Option<String> {
+        let entry = self.entries.get(self.selected)?.clone();
+        match entry.kind {
+            EntryKind::Recent | EntryKind::Rom => Some(entry.path.to_string_lossy().into_owned()),
+            EntryKind::Parent | EntryKind::Directory => {
+                self.dir = entry.path;
+                self.refresh();
+                None
+            },
+        }
+    }
+
+    /// Goes back to the parent directory
+    fn go_back(&mut self) {
+        if let Some(parent) = self.dir.parent() {
+            let previous = self.dir.clone();
+            self.dir = parent.to_path_buf();
+            self.refresh();
+            // Selects the directory we come from
+            if let Some(index) = self.entries.iter().position(|e| e.kind == EntryKind::Directory && e.path == previous) {
+                self.select(index);
+            }
+        }
+    }
+
+    /// Runs a navigation command. Returns the path of the ROM to launch if one was chosen
+    pub fn run_command(&mut self, command: &Command) -> Option<String> {
+        match command {
+            Command::MenuUp => self.select(self.selected.saturating_sub(1)),
+            Command::MenuDown => self.select(self.selected + 1),
+            Command::MenuSelect => return self.open_selected(),
+            Command::MenuBack => self.go_back(),
+            _ => {},
+        }
+        None
+    }
+
+    /// Navigates with the CHIP-8 keypad, then runs the preview for `frames` frames.
+    /// Returns the path of the ROM to launch if one was chosen
+    pub fn update(&mut self, keys_state: &[bool], frames: u32) -> Option<String> {
+        let pressed: Vec<usize> = (0..KEYBOARD_SIZE).filter(|&k| keys_state[k] && !self.prev_keys[k]).collect();
+        self.prev_keys.copy_from_slice(&keys_state[..KEYBOARD_SIZE]);
+
+        for key in pressed {
+            let command = match key {
+                0x2 => Command::MenuUp,
+                0x8 => Command::MenuDown,
+                0x5 | 0x6 => Command::MenuSelect,
+                0x4 => Command::MenuBack,
+                _ => continue,
+            };
+            if let Some(rom) = self.run_command(&command) {
+                return Some(rom);
+            }
+        }
+
+        if let Some(preview) = &mut self.preview {
+            preview.run(frames, self.instructions_per_frame);
+        }
+        None
+    }
+
+    /// Draws the launcher into a new `width` x `height` frame
+    pub fn draw(&self, width: usize, height: usize) -> Frame {
+        let mut frame = Frame::new(width, height);
+        let scale = (height / (HEIGHT * 5)).max(1);
+        let line = LINE_HEIGHT * scale;
+        let margin = 2 * scale;
+
+        // Header and help
+        let header = format!("Select a ROM - {}", self.dir.display());
+        font::draw_text(&mut frame, margin, margin, &fit(&header, width.saturating_sub(2 * margin) / (CHAR_WIDTH * scale)), scale, TEXT_COLOR);
+        let help = "Arrows / 2 8: move  Enter / 5: play  Esc: quit";
+        font::draw_text(&mut frame, margin, height.saturating_sub(line), &fit(help, width.saturating_sub(2 * margin) / (CHAR_WIDTH * scale)), scale, DIM_COLOR);
+
+        // List, scrolled so the selected entry is visible
+        let list_top = margin + line * 3 / 2;
+        let list_width = width * 11 / 20;
+        let rows = (height.saturating_sub(list_top + line * 2) / line).max(1);
+        let first = (self.selected + 1).saturating_sub(rows);
+        let max_chars = list_width.saturating_sub(2 * margin) / (CHAR_WIDTH * scale);
+        for (row, (index, entry)) in self.entries.iter().enumerate().skip(first).take(rows).enumerate() {
+            let y = list_top + row * line;
+            if index == self.selected {
+                frame.fill_rect(margin / 2, y, list_width.saturating_sub(margin), line, SELECTION_COLOR);
+            }
+            font::draw_text(&mut frame, margin, y + scale, &fit(&entry.label(), max_chars), scale, TEXT_COLOR);
+        }
+        if self.entries.is_empty() {
+            font::draw_text(&mut frame, margin, list_top + scale, "No ROM found", scale, DIM_COLOR);
+        }
+
+        // Preview and details of the selected ROM
+        let panel_x = list_width + margin;
+        let panel_width = width.saturating_sub(panel_x + margin);
+        let pixel = (panel_width / WIDTH).max(1);
+        if let Some(preview) = &self.preview {
+            frame.fill_rect(panel_x - 1, list_top - 1, WIDTH * pixel + 2, HEIGHT * pixel + 2, BORDER_COLOR);
+            for (i, &p) in preview.screen.pixels.iter().enumerate() {
+                let color = if p == 1 { PIXEL_ON } else { PIXEL_OFF };
+                frame.fill_rect(panel_x + (i % WIDTH) * pixel, list_top + (i / WIDTH) * pixel, pixel, pixel, color);
+            }
+        }
+        if let Some(entry) = self.entries.get(self.selected) {
+            let max_chars = panel_width / (CHAR_WIDTH * scale);
+            let y = list_top + HEIGHT * pixel + line / 2;
+            font::draw_text(&mut frame, panel_x, y, &fit(&entry.title, max_chars), scale, TEXT_COLOR);
+            font::draw_text(&mut frame, panel_x, y + line, &fit(&entry.platform, max_chars), scale, DIM_COLOR);
+        }
+
+        frame
+    }
+}
+
+/// Cuts `text` to `max_chars` characters, ending with `..` if it is too long
+fn fit(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+    let mut fitted: String = text.chars().take(max_chars.saturating_sub(2)).collect();
+    fitted.push_str("..");
+    fitted
+}
@@ -0,0 +1,11 @@
+//! Core of the CHIP-8 VM: the CPU, the RAM, the screen and the keypad, without any frontend.
+//! The emulator is built on it, and the fuzzing target runs it directly
+
+pub mod cpu;
+pub mod ram;
+pub mod screen;
+pub mod keypad;
+pub mod quirks;
+pub mod sanitizer;
+pub mod error;
+pub mod utils;
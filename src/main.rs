@@ -1,19 +1,37 @@
 mod chip8;
-mod cpu;
-mod ram;
+mod config;
 mod handler;
-mod screen;
-mod keypad;
-mod utils;
+mod frame;
+mod frame_timer;
+mod launcher;
+mod rom_watcher;
+mod font;
+mod osd;
+mod keypad_view;
+mod virtual_keypad;
+mod input_layer;
+mod scaler;
+mod filters;
+
+// The core of the VM is the library of the crate
+use yac8e::{cpu, ram, screen, keypad, quirks, sanitizer, error, utils};
 
 use crate::chip8::{Chip8, Chip8Config};
-use crate::handler::HandlerType;
+use crate::config::{ConfigFile, RomArguments, DEFAULT_CONFIG_FILE, ROM_SETTINGS};
+use crate::handler::{HandlerType, Bindings, ToneConfig, Waveform};
+use crate::scaler::ScalerType;
+use crate::osd::OsdMode;
+use crate::virtual_keypad::VirtualKeypadMode;
+use crate::sanitizer::StrictMode;
+use crate::error::Chip8Error;
+use crate::input_layer::Autofire;
 
 use std::error::Error;
 use std::path::Path;
 use std::process;
+use std::str::FromStr;
 
-use clap::{App, Arg};
+use clap::{App, Arg, ArgMatches};
 
 fn main() -> Result<(), Box<dyn Error>>{
     let version = "0.1";
@@ -28,8 +46,30 @@ fn main() -> Result<(), Box<dyn Error>>{
             .arg(
                 Arg::with_name("ROM_FILE")
                     .index(1)
-                    .required(true)
-                    .help("The ROM file to run")
+                    .help("The ROM file to run, the ROM launcher is shown if not set")
+            )
+            .arg(
+                Arg::with_name("watch")
+                    .short("w")
+                    .long("watch")
+                    .help("Reloads the ROM and resets the machine when the ROM file changes")
+            )
+            .arg(
+                Arg::with_name("auto-pause")
+                    .long("auto-pause")
+                    .help("Pauses the emulation and mutes the sound while the window doesn't have the focus or is minimized")
+            )
+            .arg(
+                Arg::with_name("random-ram")
+                    .long("random-ram")
+                    .help("Fills the RAM with random bytes before loading the ROM, like the uninitialized RAM of real hardware")
+            )
+            .arg(
+                Arg::with_name("rom-dir")
+                    .long("rom-dir")
+                    .default_value(".")
+                    .value_name("DIR")
+                    .help("Sets the directory browsed by the ROM launcher")
             )
             .arg(
                 Arg::with_name("debug")
@@ -46,7 +86,7 @@ fn main() -> Result<(), Box<dyn Error>>{
                     .default_value("sdl")
                     .value_name("LIBRARY")
                     .takes_value(true)
-                    .help("Sets the handling library to use (minifb doesn't support sounds)")
+                    .help("Sets the handling library to use")
             )
             .arg(
                 Arg::with_name("hertz")
@@ -54,7 +94,48 @@ fn main() -> Result<(), Box<dyn Error>>{
                     .long("hertz")
                     .default_value("500")
                     .value_name("HERTZ")
-                    .help("Sets the Hertz value for the CPU clock cycle per second speed")
+                    .help("Sets the Hertz value for the CPU clock cycle per second speed, run as instructions per 60 Hz frame")
+            )
+            .arg(
+                Arg::with_name("ipf")
+                    .short("i")
+                    .long("ipf")
+                    .value_name("INSTRUCTIONS")
+                    .help("Sets the number of instructions per 60 Hz frame, overrides --hertz")
+            )
+            .arg(
+                Arg::with_name("fast-forward")
+                    .long("fast-forward")
+                    .default_value("uncapped")
+                    .value_name("SPEED")
+                    .help("Sets the speed multiplier of the fast-forward (Tab held, F6 toggles), or uncapped")
+            )
+            .arg(
+                Arg::with_name("slow-motion")
+                    .long("slow-motion")
+                    .default_value("0.25")
+                    .value_name("SPEED")
+                    .help("Sets the speed multiplier of the slow motion (F7 toggles)")
+            )
+            .arg(
+                Arg::with_name("vsync")
+                    .long("vsync")
+                    .help("Presents the frames with vsync, with the SDL backend (requires a 60 Hz display)")
+            )
+            .arg(
+                Arg::with_name("keypad-overlay")
+                    .long("keypad-overlay")
+                    .help("Draws the keypad over the screen, with the pressed keys and the keys tested by the ROM (F4 toggles)")
+            )
+            .arg(
+                Arg::with_name("virtual-keypad")
+                    .long("virtual-keypad")
+                    .possible_value("none")
+                    .possible_value("side")
+                    .possible_value("bottom")
+                    .default_value("none")
+                    .value_name("POSITION")
+                    .help("Draws a keypad clickable with the mouse or by touch beside or under the screen")
             )
             .arg(
                 Arg::with_name("width")
@@ -70,6 +151,144 @@ fn main() -> Result<(), Box<dyn Error>>{
                     .value_name("HEIGHT")
                     .help("Sets the window height")
             )
+            .arg(
+                Arg::with_name("scaler")
+                    .short("s")
+                    .long("scaler")
+                    .possible_value("nearest")
+                    .possible_value("scale2x")
+                    .possible_value("scale3x")
+                    .possible_value("epx")
+                    .possible_value("xbr")
+                    .default_value("nearest")
+                    .value_name("SCALER")
+                    .takes_value(true)
+                    .help("Sets the software upscaling filter applied to the screen")
+            )
+            .arg(
+                Arg::with_name("filters")
+                    .short("f")
+                    .long("filters")
+                    .default_value("")
+                    .value_name("FILTERS")
+                    .help("Sets the post-processing filters, comma-separated (scanlines[:intensity], grid[:intensity], vignette[:strength], crt[:curvature[:bloom]])")
+            )
+            .arg(
+                Arg::with_name("seed")
+                    .long("seed")
+                    .value_name("SEED")
+                    .takes_value(true)
+                    .help("Seeds the random generator for a deterministic emulation, and locks the instructions per frame")
+            )
+            .arg(
+                Arg::with_name("strict")
+                    .long("strict")
+                    .possible_value("off")
+                    .possible_value("warn")
+                    .possible_value("halt")
+                    .default_value("off")
+                    .value_name("MODE")
+                    .help("Reports the behaviors real hardware wouldn't tolerate (stack overflow, I out of range, writes below 0x200, self-modifying code, reads of never written memory, execution outside of the ROM), and halts on them")
+            )
+            .arg(
+                Arg::with_name("quirks")
+                    .short("q")
+                    .long("quirks")
+                    .default_value("")
+                    .value_name("QUIRKS")
+                    .help("Enables behaviors of other interpreters, comma-separated (key-wait-press: FX0A returns on key press instead of release)")
+            )
+            .arg(
+                Arg::with_name("autofire")
+                    .long("autofire")
+                    .default_value("")
+                    .value_name("KEYS")
+                    .help("Sets the keys pressed and released automatically while held, comma-separated CHIP-8 keys with their rate in presses per second, 10 if not set (e.g. 5:10,A)")
+            )
+            .arg(
+                Arg::with_name("bindings")
+                    .short("b")
+                    .long("bindings")
+                    .default_value("")
+                    .value_name("BINDINGS")
+                    .help("Changes the hotkeys, comma-separated actions with their space-separated hotkeys (e.g. \"save-state=ctrl+s f5, quit=q\")")
+            )
+            .arg(
+                Arg::with_name("osd")
+                    .long("osd")
+                    .possible_value("off")
+                    .possible_value("messages")
+                    .possible_value("stats")
+                    .default_value("messages")
+                    .value_name("MODE")
+                    .help("Sets what the on-screen display shows (F3 switches it): nothing, messages, or messages and stats")
+            )
+            .arg(
+                Arg::with_name("tone-frequency")
+                    .long("tone-frequency")
+                    .default_value("440")
+                    .value_name("HZ")
+                    .help("Sets the frequency of the beep")
+            )
+            .arg(
+                Arg::with_name("waveform")
+                    .long("waveform")
+                    .possible_value("square")
+                    .possible_value("pulse")
+                    .possible_value("triangle")
+                    .possible_value("sine")
+                    .possible_value("noise")
+                    .default_value("square")
+                    .value_name("WAVEFORM")
+                    .help("Sets the waveform of the beep")
+            )
+            .arg(
+                Arg::with_name("pulse-width")
+                    .long("pulse-width")
+                    .default_value("0.25")
+                    .value_name("WIDTH")
+                    .help("Sets the width of the pulse waveform, between 0 and 1")
+            )
+            .arg(
+                Arg::with_name("volume")
+                    .long("volume")
+                    .default_value("0.25")
+                    .value_name("VOLUME")
+                    .help("Sets the volume of the beep, between 0 and 1 (M mutes, - and = change the volume)")
+            )
+            .arg(
+                Arg::with_name("attack")
+                    .long("attack")
+                    .default_value("5")
+                    .value_name("MS")
+                    .help("Sets the time taken by the beep to reach its volume")
+            )
+            .arg(
+                Arg::with_name("release")
+                    .long("release")
+                    .default_value("5")
+                    .value_name("MS")
+                    .help("Sets the time taken by the beep to fade out")
+            )
+            .arg(
+                Arg::with_name("video-driver")
+                    .long("video-driver")
+                    .value_name("DRIVER")
+                    .help("Sets the SDL video driver (e.g. x11, wayland, or dummy / offscreen to run without any display)")
+            )
+            .arg(
+                Arg::with_name("record-audio")
+                    .long("record-audio")
+                    .value_name("WAV_FILE")
+                    .help("Records the sound into a WAV file, F9 starts / stops the recording [default: yac8e.wav when started with F9]")
+            )
+            .arg(
+                Arg::with_name("config")
+                    .short("c")
+                    .long("config")
+                    .value_name("CONFIG_FILE")
+                    .help("Sets the config file, command line options override its settings [default: yac8e.ini if it exists]")
+            )
             .get_matches();
 
     let rom = match matches.value_of("ROM_FILE") {
@@ -78,20 +297,32 @@ fn main() -> Result<(), Box<dyn Error>>{
                 eprintln!("[-] File does not exist");
                 process::exit(1);
             }
-            String::from(f)
+            Some(String::from(f))
         },
-        None => {
-            eprintln!("[-] Argument parsing error");
+        None => None,
+    };
+
+    // Without a ROM, only the global settings of the config file apply
+    let rom_name = rom.as_deref().unwrap_or("");
+    let config_file = match matches.value_of("config") {
+        Some(c) => Some(c.to_string()),
+        None if Path::new(DEFAULT_CONFIG_FILE).exists() => Some(DEFAULT_CONFIG_FILE.to_string()),
+        None => None,
+    };
+    let config = match &config_file {
+        Some(c) => ConfigFile::load(Path::new(c), rom_name).unwrap_or_else(|e| {
+            eprintln!("[-] {}", e);
             process::exit(1);
-        }
+        }),
+        None => ConfigFile::default(),
     };
 
     let debug = match matches.occurrences_of("debug") {
-        0 => false,
+        0 => config.get("debug") == Some("true"),
         _ => true,
     };
 
-    let handler_type = match matches.value_of("library") {
+    let handler_type = match get_value(&matches, &config, "library") {
         Some(d) => {
             match d {
                 "minifb" => HandlerType::MINIFB,
@@ -111,15 +342,56 @@ fn main() -> Result<(), Box<dyn Error>>{
     // 500 Hz is considered a good value for CHIP-8 emulators.
     // This mean roughly that 1 clock cycle ~= 2ms
     // (This may vary depending on the instruction, i.e: drawing a sprite costs more than a simple XOR operation)
-    let hertz: f64 = match matches.value_of("hertz") {
+    // The settings following the ROM are resolved again with the config file when another ROM is opened
+    let mut rom_arguments = RomArguments::default();
+    for name in ROM_SETTINGS.iter() {
+        rom_arguments.insert(name, matches.value_of(name), matches.occurrences_of(name) > 0);
+    }
+    let rom_settings = rom_arguments.resolve(&config).unwrap_or_else(|e| {
+        eprintln!("\n[-] {}\n", e);
+        process::exit(1);
+    });
+
+    let fast_forward = match get_value(&matches, &config, "fast-forward") {
+        Some("uncapped") => None,
+        value => Some(parse_number(value, "fast-forward speed", 1.0, 100.0)),
+    };
+    let slow_motion: f64 = parse_number(get_value(&matches, &config, "slow-motion"), "slow motion speed", 0.01, 1.0);
+
+    let watch = match matches.occurrences_of("watch") {
+        0 => config.get("watch") == Some("true"),
+        _ => true,
+    };
+
+    let auto_pause = match matches.occurrences_of("auto-pause") {
+        0 => config.get("auto-pause") == Some("true"),
+        _ => true,
+    };
+
+    let random_ram = match matches.occurrences_of("random-ram") {
+        0 => config.get("random-ram") == Some("true"),
+        _ => true,
+    };
+
+    let vsync = match matches.occurrences_of("vsync") {
+        0 => config.get("vsync") == Some("true"),
+        _ => true,
+    };
+
+    let keypad_overlay = match matches.occurrences_of("keypad-overlay") {
+        0 => config.get("keypad-overlay") == Some("true"),
+        _ => true,
+    };
+
+    let width: usize = match get_value(&matches, &config, "width") {
         Some(t) => {
             match t.parse().unwrap_or_else(|_| {
-                eprintln!("\n[-] Invalid Hertz value\n");
+                eprintln!("\n[-] Invalid width value\n");
                 process::exit(1);
             }) {
-                x if x > 0.0 && x < 100000.0 => x,
+                x if x > 0 && x < 10000 => x,
                 _ => {
-                    eprintln!("\n[-] Invalid Hertz value\n");
+                    eprintln!("\n[-] Invalid width value\n");
                     process::exit(1);
                 }
             }
@@ -130,15 +402,15 @@ fn main() -> Result<(), Box<dyn Error>>{
         }
     };
 
-    let width: usize = match matches.value_of("width") {
+    let height: usize = match get_value(&matches, &config, "height") {
         Some(t) => {
             match t.parse().unwrap_or_else(|_| {
-                eprintln!("\n[-] Invalid width value\n");
+                eprintln!("\n[-] Invalid height value\n");
                 process::exit(1);
             }) {
                 x if x > 0 && x < 10000 => x,
                 _ => {
-                    eprintln!("\n[-] Invalid width value\n");
+                    eprintln!("\n[-] Invalid height value\n");
                     process::exit(1);
                 }
             }
@@ -149,15 +421,123 @@ fn main() -> Result<(), Box<dyn Error>>{
         }
     };
 
-    let height: usize = match matches.value_of("height") {
-        Some(t) => {
-            match t.parse().unwrap_or_else(|_| {
-                eprintln!("\n[-] Invalid height value\n");
+    let scaler = match get_value(&matches, &config, "scaler") {
+        Some(s) => {
+            match s {
+                "nearest" => ScalerType::NEAREST,
+                "scale2x" => ScalerType::SCALE2X,
+                "scale3x" => ScalerType::SCALE3X,
+                "epx" => ScalerType::EPX,
+                "xbr" => ScalerType::XBR,
+                _ => {
+                    eprintln!("\n[-] Invalid scaler value\n");
+                    process::exit(1);
+                }
+            }
+        },
+        None => {
+            eprintln!("[-] Argument parsing error");
+            process::exit(1);
+        }
+    };
+
+    let strict = match get_value(&matches, &config, "strict") {
+        Some(s) => {
+            match s {
+                "off" => StrictMode::OFF,
+                "warn" => StrictMode::WARN,
+                "halt" => StrictMode::HALT,
+                _ => {
+                    eprintln!("\n[-] Invalid strict value\n");
+                    process::exit(1);
+                }
+            }
+        },
+        None => {
+            eprintln!("[-] Argument parsing error");
+            process::exit(1);
+        }
+    };
+
+    let osd = match get_value(&matches, &config, "osd") {
+        Some(o) => {
+            match o {
+                "off" => OsdMode::OFF,
+                "messages" => OsdMode::MESSAGES,
+                "stats" => OsdMode::STATS,
+                _ => {
+                    eprintln!("\n[-] Invalid osd value\n");
+                    process::exit(1);
+                }
+            }
+        },
+        None => {
+            eprintln!("[-] Argument parsing error");
+            process::exit(1);
+        }
+    };
+
+    let virtual_keypad = match get_value(&matches, &config, "virtual-keypad") {
+        Some(v) => {
+            match v {
+                "none" => VirtualKeypadMode::NONE,
+                "side" => VirtualKeypadMode::SIDE,
+                "bottom" => VirtualKeypadMode::BOTTOM,
+                _ => {
+                    eprintln!("\n[-] Invalid virtual keypad value\n");
+                    process::exit(1);
+                }
+            }
+        },
+        None => {
+            eprintln!("[-] Argument parsing error");
+            process::exit(1);
+        }
+    };
+
+    let autofire = match get_value(&matches, &config, "autofire") {
+        Some(a) => {
+            Autofire::parse(a).unwrap_or_else(|e| {
+                eprintln!("\n[-] Invalid autofire value: {}\n", e);
                 process::exit(1);
-            }) {
-                x if x > 0 && x < 10000 => x,
+            })
+        },
+        None => {
+            eprintln!("[-] Argument parsing error");
+            process::exit(1);
+        }
+    };
+
+    let bindings = match get_value(&matches, &config, "bindings") {
+        Some(b) => {
+            Bindings::parse(b).unwrap_or_else(|e| {
+                eprintln!("\n[-] Invalid bindings value: {}\n", e);
+                process::exit(1);
+            })
+        },
+        None => {
+            eprintln!("[-] Argument parsing error");
+            process::exit(1);
+        }
+    };
+
+    let seed = get_value(&matches, &config, "seed").map(|s| parse_number::<u64>(Some(s), "seed", 0, u64::MAX));
+
+    let video_driver = get_value(&matches, &config, "video-driver").map(String::from);
+    let rom_dir = get_value(&matches, &config, "rom-dir").unwrap_or(".").to_string();
+    let record_audio = get_value(&matches, &config, "record-audio").map(String::from);
+
+    let pulse_width: f32 = parse_number(get_value(&matches, &config, "pulse-width"), "pulse width", 0.0, 1.0);
+    let waveform = match get_value(&matches, &config, "waveform") {
+        Some(w) => {
+            match w {
+                "square" => Waveform::SQUARE,
+                "pulse" => Waveform::PULSE { duty: pulse_width },
+                "triangle" => Waveform::TRIANGLE,
+                "sine" => Waveform::SINE,
+                "noise" => Waveform::NOISE,
                 _ => {
-                    eprintln!("\n[-] Invalid height value\n");
+                    eprintln!("\n[-] Invalid waveform value\n");
                     process::exit(1);
                 }
             }
@@ -168,21 +548,94 @@ fn main() -> Result<(), Box<dyn Error>>{
         }
     };
 
+    let tone = ToneConfig {
+        frequency: parse_number(get_value(&matches, &config, "tone-frequency"), "tone frequency", 1.0, 20000.0),
+        waveform: waveform,
+        volume: parse_number(get_value(&matches, &config, "volume"), "volume", 0.0, 1.0),
+        attack: parse_number::<f32>(get_value(&matches, &config, "attack"), "attack", 0.0, 1000.0) / 1000.0,
+        release: parse_number::<f32>(get_value(&matches, &config, "release"), "release", 0.0, 1000.0) / 1000.0,
+    };
+
     let chip8_config = Chip8Config {
         rom: rom,
+        rom_dir: rom_dir,
+        watch: watch,
+        auto_pause: auto_pause,
         debug: debug,
+        quirks: rom_settings.quirks,
+        seed: seed,
+        strict: strict,
+        random_ram: random_ram,
         handler_type: handler_type,
-        hertz: hertz,
+        instructions_per_frame: rom_settings.instructions_per_frame,
+        vsync: vsync,
+        fast_forward: fast_forward,
+        slow_motion: slow_motion,
         window_width: width,
         window_height: height,
+        scaler: scaler,
+        filters: rom_settings.filters,
+        osd: osd,
+        keypad_overlay: keypad_overlay,
+        virtual_keypad: virtual_keypad,
+        autofire: autofire,
+        bindings: bindings,
+        video_driver: video_driver,
+        tone: tone,
+        record_audio: record_audio,
+        config_file: config_file,
+        rom_arguments: rom_arguments,
     };
 
     println!("chip8_config: {:#?}", chip8_config);
 
     if let Err(e) = Chip8::run_rom(chip8_config) {
-        eprintln!("[-] An error occured: {}", e);
-        process::exit(1);
+        // The errors of the VM exit with their own code, so scripts can tell a faulty ROM from an emulator failure
+        match e.downcast_ref::<Chip8Error>() {
+            Some(Chip8Error::UnknownOpcode { pc, opcode }) => {
+                eprintln!("[-] The ROM ran the unknown instruction {:04X} at {:#05X}", opcode, pc);
+                process::exit(2);
+            },
+            Some(Chip8Error::RomTooLarge { size, max }) => {
+                eprintln!("[-] The ROM doesn't fit in memory: {} bytes, {} bytes at most", size, max);
+                process::exit(2);
+            },
+            Some(e) => {
+                eprintln!("[-] The ROM crashed the VM: {}", e);
+                process::exit(2);
+            },
+            None => {
+                eprintln!("[-] An error occured: {}", e);
+                process::exit(1);
+            },
+        }
     }
 
     Ok(())
 }
+
+/// Returns the value of the argument `name`
+/// An argument given on the command line overrides the config file, which overrides the default value
+fn get_value<'a>(matches: &'a ArgMatches, config: &'a ConfigFile, name: &str) -> Option<&'a str> {
+    if matches.occurrences_of(name) == 0 {
+        if let Some(v) = config.get(name) {
+            return Some(v);
+        }
+    }
+    matches.value_of(name)
+}
+
+/// Parses the numeric argument `value`, exits if it is invalid or not between `min` and `max`
+fn parse_number<T: FromStr + PartialOrd>(value: Option<&str>, name: &str, min: T, max: T) -> T {
+    match value.map(|v| v.parse::<T>()) {
+        Some(Ok(x)) if x >= min && x <= max => x,
+        Some(_) => {
+            eprintln!("\n[-] Invalid {} value\n", name);
+            process::exit(1);
+        },
+        None => {
+            eprintln!("[-] Argument parsing error");
+            process::exit(1);
+        }
+    }
+}
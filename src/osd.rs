@@ -0,0 +1,139 @@
+use crate::frame::Frame;
+use crate::screen::HEIGHT;
+use crate::font::{self, LINE_HEIGHT};
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// Time a message stays on screen
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+/// Maximum number of messages on screen, the oldest ones are dropped
+const MAX_MESSAGES: usize = 4;
+/// Time between two updates of the FPS / IPS stats
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+/// Color of the text
+const TEXT_COLOR: u32 = 0xFFFFFF;
+/// Darkening of the background behind the text (0.0 - 1.0)
+const BACKGROUND_SHADE: f32 = 0.6;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// What the on-screen display shows
+pub enum OsdMode {
+    /// Nothing
+    OFF,
+    /// The messages and the status
+    MESSAGES,
+    /// The messages, the status and the stats
+    STATS,
+}
+
+#[derive(Debug)]
+/// On-screen display, drawn over the displayed frame but never into the CHIP-8 screen
+///
+/// Shows transient messages at the bottom left, a status (e.g. the speed) at the top right,
+/// and optionally stats at the top left
+pub struct Osd {
+    /// What is shown
+    mode: OsdMode,
+    /// Messages and the time they expire at, the oldest first
+    messages: VecDeque<(String, Instant)>,
+    /// Emulated frames per second, measured
+    fps: f64,
+    /// Instructions per second, measured
+    ips: f64,
+    /// Time of the last stats update
+    last_sample: Instant,
+    /// Frames and instructions counters at the last stats update
+    last_counters: (u64, u64),
+}
+
+impl Osd {
+    /// Creates a new `Osd` object
+    pub fn new(mode: OsdMode) -> Self {
+        Osd {
+            mode: mode,
+            messages: VecDeque::new(),
+            fps: 0.0,
+            ips: 0.0,
+            last_sample: Instant::now(),
+            last_counters: (0, 0),
+        }
+    }
+
+    /// Switches to the next mode: off, messages, stats. Returns the new mode
+    pub fn cycle_mode(&mut self) -> OsdMode {
+        self.mode = match self.mode {
+            OsdMode::OFF => OsdMode::MESSAGES,
+            OsdMode::MESSAGES => OsdMode::STATS,
+            OsdMode::STATS => OsdMode::OFF,
+        };
+        self.mode
+    }
+
+    /// Shows `text` for a few seconds
+    pub fn notify<T: AsRef<str>>(&mut self, text: T) {
+        self.messages.push_back((text.as_ref().to_string(), Instant::now() + MESSAGE_DURATION));
+        while self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+
+    /// Drops the expired messages and updates the stats from the total numbers of emulated `frames` and `instructions`.
+    /// Returns whether the display changed and must be presented again
+    pub fn update(&mut self, frames: u64, instructions: u64) -> bool {
+        let now = Instant::now();
+        let count = self.messages.len();
+        self.messages.retain(|(_, expires)| *expires > now);
+        let mut changed = self.messages.len() != count;
+
+        let elapsed = now - self.last_sample;
+        if elapsed >= STATS_INTERVAL {
+            let (last_frames, last_instructions) = self.last_counters;
+            self.fps = frames.saturating_sub(last_frames) as f64 / elapsed.as_secs_f64();
+            self.ips = (instructions - last_instructions) as f64 / elapsed.as_secs_f64();
+            self.last_sample = now;
+            self.last_counters = (frames, instructions);
+            changed |= self.mode == OsdMode::STATS;
+        }
+        changed && self.mode != OsdMode::OFF
+    }
+
+    /// Whether the stats are shown, in which case the frame must be presented every time the emulator state changes
+    pub fn shows_stats(&self) -> bool {
+        self.mode == OsdMode::STATS
+    }
+
+    /// Draws the OSD over `frame`: the messages, the `status` if any, and the stats followed by `stats_lines`
+    pub fn draw(&self, frame: &mut Frame, status: Option<&str>, stats_lines: &[String]) {
+        if self.mode == OsdMode::OFF {
+            return;
+        }
+        let scale = (frame.height / (HEIGHT * 5)).max(1);
+        let line = LINE_HEIGHT * scale;
+        let margin = 2 * scale;
+
+        if let Some(status) = status {
+            let x = frame.width.saturating_sub(font::text_width(status, scale) + scale + margin);
+            draw_line(frame, x, margin, status, scale);
+        }
+
+        if self.mode == OsdMode::STATS {
+            let mut lines = vec![format!("FPS: {:.1}", self.fps), format!("IPS: {:.0}", self.ips)];
+            lines.extend_from_slice(stats_lines);
+            for (i, text) in lines.iter().enumerate() {
+                draw_line(frame, margin, margin + i * line, text, scale);
+            }
+        }
+
+        let bottom = frame.height.saturating_sub(margin + self.messages.len() * line);
+        for (i, (text, _)) in self.messages.iter().enumerate() {
+            draw_line(frame, margin, bottom + i * line, text, scale);
+        }
+    }
+}
+
+/// Draws a line of text on a darkened background, with its top-left corner at (`x`,`y`)
+fn draw_line(frame: &mut Frame, x: usize, y: usize, text: &str, scale: usize) {
+    frame.shade_rect(x, y, font::text_width(text, scale) + scale, LINE_HEIGHT * scale, BACKGROUND_SHADE);
+    font::draw_text(frame, x + scale, y + scale, text, scale, TEXT_COLOR);
+}
@@ -0,0 +1,34 @@
+use std::error::Error;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+/// Behaviors differing between CHIP-8 interpreters, all disabled by default (COSMAC VIP behavior)
+pub struct Quirks {
+    /// `FX0A` returns as soon as a key is pressed, instead of waiting for the key to be released
+    pub key_wait_press: bool,
+}
+
+impl Quirks {
+    /// Parses a comma-separated list of quirks to enable, e.g. `key-wait-press`
+    pub fn parse(s: &str) -> Result<Self, Box<dyn Error>> {
+        let mut quirks = Quirks::default();
+        for name in s.split(',').map(|q| q.trim().to_lowercase()).filter(|q| !q.is_empty()) {
+            match name.as_str() {
+                "key-wait-press" => quirks.key_wait_press = true,
+                _ => return Err(format!("Unknown quirk: {}", name).into()),
+            }
+        }
+        Ok(quirks)
+    }
+
+    /// Describes the enabled quirks, e.g. `key-wait-press`, or `none`
+    pub fn description(&self) -> String {
+        let mut names = Vec::new();
+        if self.key_wait_press {
+            names.push("key-wait-press");
+        }
+        if names.is_empty() {
+            return "none".to_string();
+        }
+        names.join(", ")
+    }
+}
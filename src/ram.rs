@@ -1,19 +1,30 @@
-use crate::utils::{oob_write_error, oob_read_error, integer_overflow_error};
+use crate::error::Chip8Error;
 
-use std::error::Error;
+use rand::Rng;
 
-#[derive(Debug)]
+/// Size of the RAM, in bytes
+pub const MEMORY_SIZE: usize = 4096;
+/// Size of the builtin font, loaded at the start of the RAM
+pub const FONT_SIZE: usize = 80;
+
+#[derive(Clone, Debug)]
 /// Struct emulating the CHIP-8 RAM
 pub struct Ram {
     /// Memory vector
     memory: Vec<u8>,
 }
 
+impl Default for Ram {
+    fn default() -> Self {
+        Ram::new()
+    }
+}
+
 impl Ram {
     /// Creates a new `Ram` object
     pub fn new() -> Self {
         let mut ram = Ram {
-            memory: vec![0u8; 4096],
+            memory: vec![0u8; MEMORY_SIZE],
         };
         // We load the builtins sprites
         Ram::load_sprites(&mut ram.memory);
@@ -24,7 +35,7 @@ impl Ram {
     /// Loads the builtins font utilities to allow for simple output of common characters in memory
     /// To use these sprites, the opcode FX29 must be used
     fn load_sprites(memory: &mut [u8]) {
-        let sprites: [u8; 80] = [
+        let sprites: [u8; FONT_SIZE] = [
             0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
             0x20, 0x60, 0x20, 0x20, 0x70, // 1
             0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
@@ -45,25 +56,30 @@ impl Ram {
         memory[0..sprites.len()].copy_from_slice(&sprites);
     }
 
+    /// Fills the RAM but the font with random bytes, like the uninitialized RAM of real hardware
+    pub fn fill_random<R: Rng>(&mut self, rng: &mut R) {
+        rng.fill(&mut self.memory[FONT_SIZE..]);
+    }
+
     /// Writes a single byte into memory at `address` with value `value`
-    pub fn write_byte(&mut self, address: usize, value: u8) -> Result<(), Box<dyn Error>> {
+    pub fn write_byte(&mut self, address: usize, value: u8) -> Result<(), Chip8Error> {
         *self.memory.get_mut(address).ok_or(
-            oob_write_error(address, &[value])
+            Ram::write_error(address, 1)
         )? = value;
         Ok(())
     }
 
     #[allow(dead_code)]
     /// Writes multiple bytes into memory at `address` with value `buf`
-    pub fn write_bytes(&mut self, address: usize, buf: &[u8]) -> Result<(), Box<dyn Error>> {
+    pub fn write_bytes(&mut self, address: usize, buf: &[u8]) -> Result<(), Chip8Error> {
         self.memory
             .get_mut(
                 address..address.checked_add(buf.len()).ok_or(
-                    integer_overflow_error(address, buf.len())
+                    Ram::write_error(address, buf.len())
                 )?
             )
             .ok_or(
-                oob_write_error(address, buf)
+                Ram::write_error(address, buf.len())
             )?
             .copy_from_slice(buf);
 
@@ -71,19 +87,35 @@ impl Ram {
     }
 
     /// Reads a single byte at `address`
-    pub fn read_byte(&self, address: usize) -> Result<u8, Box<dyn Error>>{
+    pub fn read_byte(&self, address: usize) -> Result<u8, Chip8Error>{
         let v = self.memory.get(address).ok_or(
-            oob_read_error(address, 1)
+            Ram::read_error(address, 1)
         )?;
         Ok(*v)
     }
 
     #[allow(dead_code)]
     /// Reads `size` bytes at `address`
-    pub fn read_bytes(&self, address: usize, size: usize) -> Result<&[u8], Box<dyn Error>>{
-        let v = self.memory.get(address..address+size).ok_or(
-            oob_read_error(address, size)
-        )?;
+    pub fn read_bytes(&self, address: usize, size: usize) -> Result<&[u8], Chip8Error>{
+        let v = self.memory
+            .get(
+                address..address.checked_add(size).ok_or(
+                    Ram::read_error(address, size)
+                )?
+            )
+            .ok_or(
+                Ram::read_error(address, size)
+            )?;
         Ok(v)
     }
-}
\ No newline at end of file
+
+    /// Error for a read of `size` bytes at `address`, the CPU sets the instruction that did it
+    fn read_error(address: usize, size: usize) -> Chip8Error {
+        Chip8Error::OutOfBoundsRead { pc: None, opcode: None, address: address, size: size }
+    }
+
+    /// Error for a write of `size` bytes at `address`, the CPU sets the instruction that did it
+    fn write_error(address: usize, size: usize) -> Chip8Error {
+        Chip8Error::OutOfBoundsWrite { pc: None, opcode: None, address: address, size: size }
+    }
+}
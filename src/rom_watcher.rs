@@ -0,0 +1,66 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Time between two checks of the ROM file
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+#[derive(Debug)]
+/// Watches a ROM file to reload it when it is rewritten, e.g. by an assembler
+///
+/// The modification time of the file is polled. A change is only reported once the modification time
+/// is the same on two checks in a row, so a file still being written isn't loaded
+pub struct RomWatcher {
+    /// Path of the watched ROM
+    path: PathBuf,
+    /// Modification time of the loaded ROM
+    loaded: Option<SystemTime>,
+    /// Modification time seen at the last check, if it differs from the loaded one
+    pending: Option<SystemTime>,
+    /// Last time the file was checked
+    last_check: Instant,
+}
+
+impl RomWatcher {
+    /// Creates a new `RomWatcher` object watching the ROM at `path`, which was just loaded
+    pub fn new(path: &str) -> Self {
+        let path = PathBuf::from(path);
+        RomWatcher {
+            loaded: RomWatcher::modified(&path),
+            path: path,
+            pending: None,
+            last_check: Instant::now(),
+        }
+    }
+
+    /// Returns the modification time of the file at `path`, `None` if it can't be read (e.g. being replaced)
+    fn modified(path: &Path) -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    }
+
+    /// Checks the file if the poll interval elapsed. Returns `true` when it changed and must be reloaded
+    pub fn poll(&mut self) -> bool {
+        if self.last_check.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        let modified = match RomWatcher::modified(&self.path) {
+            Some(modified) => modified,
+            None => return false,
+        };
+        if Some(modified) == self.loaded {
+            self.pending = None;
+            return false;
+        }
+
+        // The file changed, we wait for it to be stable before reloading it
+        if self.pending == Some(modified) {
+            self.loaded = Some(modified);
+            self.pending = None;
+            return true;
+        }
+        self.pending = Some(modified);
+        false
+    }
+}
@@ -0,0 +1,196 @@
+use crate::cpu::PROGRAM_START;
+use crate::ram::{MEMORY_SIZE, FONT_SIZE};
+use crate::error::Chip8Error;
+
+use std::collections::HashSet;
+use std::fmt;
+use std::mem::{self, Discriminant};
+
+/// Stack depth of the COSMAC VIP interpreter
+const STACK_SIZE: usize = 16;
+/// Highest address reachable on the COSMAC VIP
+const MAX_ADDRESS: u16 = 0xFFF;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// What the strict mode does with the behaviors real hardware wouldn't tolerate
+pub enum StrictMode {
+    /// Nothing is checked
+    OFF,
+    /// The behaviors are reported, and the emulation goes on
+    WARN,
+    /// The behaviors are reported, and the emulation is paused after the instruction
+    HALT,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+/// Undefined or suspicious behavior of a ROM
+pub enum Violation {
+    /// `2NNN` called a subroutine with `depth` return addresses on the stack
+    StackOverflow { depth: usize },
+    /// `I` was set to `i`, past the 4 KB of RAM
+    IndexOutOfRange { i: u16 },
+    /// The ROM wrote at `address`, in the font or the interpreter area below 0x200
+    ReservedWrite { address: usize },
+    /// The ROM wrote at `address`, which was executed, or executed `address` after writing it
+    SelfModifyingCode { address: usize },
+    /// The ROM read `address`, which was never written
+    UninitializedRead { address: usize },
+    /// The ROM executed `address`, outside of the loaded ROM
+    OutsideRom { address: usize },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Violation::StackOverflow { depth } => {
+                write!(f, "Stack overflow, {} return addresses for {} levels", depth, STACK_SIZE)
+            },
+            Violation::IndexOutOfRange { i } => {
+                write!(f, "I set past {:#X}: {:#X}", MAX_ADDRESS, i)
+            },
+            Violation::ReservedWrite { address } if address < FONT_SIZE => {
+                write!(f, "Write into the font at {:#05X}", address)
+            },
+            Violation::ReservedWrite { address } => {
+                write!(f, "Write into the interpreter area at {:#05X}", address)
+            },
+            Violation::SelfModifyingCode { address } => {
+                write!(f, "Self-modifying code at {:#05X}", address)
+            },
+            Violation::UninitializedRead { address } => {
+                write!(f, "Read of never written memory at {:#05X}", address)
+            },
+            Violation::OutsideRom { address } => {
+                write!(f, "Execution outside of the ROM at {:#05X}", address)
+            },
+        }
+    }
+}
+
+#[derive(Clone)]
+/// Checks the memory accesses and the stack of a running ROM, for the strict mode.
+/// The CPU tells it what each instruction does, and it reports the violations once per instruction address and kind
+pub struct Sanitizer {
+    /// Address of the byte following the loaded ROM
+    rom_end: usize,
+    /// Whether each byte of RAM was loaded (font, ROM) or written by the ROM
+    initialized: Vec<bool>,
+    /// Whether each byte of RAM was written by the ROM
+    modified: Vec<bool>,
+    /// Whether each byte of RAM was executed
+    executed: Vec<bool>,
+    /// Address of the running instruction
+    pc: u16,
+    /// Running instruction
+    opcode: u16,
+    /// Instruction addresses and kinds of the violations already reported
+    reported: HashSet<(u16, Discriminant<Violation>)>,
+    /// Violations not taken yet by the emulator
+    reports: Vec<Chip8Error>,
+}
+
+impl fmt::Debug for Sanitizer {
+    // The memory maps are too large to be shown
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sanitizer")
+            .field("rom_end", &self.rom_end)
+            .field("reported", &self.reported.len())
+            .finish()
+    }
+}
+
+impl Sanitizer {
+    /// Creates a new `Sanitizer` object for a ROM of `rom_size` bytes, loaded with the font
+    pub fn new(rom_size: usize) -> Self {
+        let rom_end = (PROGRAM_START as usize + rom_size).min(MEMORY_SIZE);
+        let mut initialized = vec![false; MEMORY_SIZE];
+        for address in (0..FONT_SIZE).chain(PROGRAM_START as usize..rom_end) {
+            initialized[address] = true;
+        }
+
+        Sanitizer {
+            rom_end: rom_end,
+            initialized: initialized,
+            modified: vec![false; MEMORY_SIZE],
+            executed: vec![false; MEMORY_SIZE],
+            pc: 0,
+            opcode: 0,
+            reported: HashSet::new(),
+            reports: Vec::new(),
+        }
+    }
+
+    /// Starts the instruction `opcode` fetched at `pc`
+    pub fn start(&mut self, pc: u16, opcode: u16) {
+        self.pc = pc;
+        self.opcode = opcode;
+
+        let address = pc as usize;
+        if address < PROGRAM_START as usize || address >= self.rom_end {
+            self.report(Violation::OutsideRom { address: address });
+        }
+        for address in address..address + 2 {
+            if self.modified.get(address) == Some(&true) {
+                self.report(Violation::SelfModifyingCode { address: address });
+            }
+            if let Some(executed) = self.executed.get_mut(address) {
+                *executed = true;
+            }
+        }
+    }
+
+    /// Checks the stack `depth` after a subroutine call
+    pub fn call(&mut self, depth: usize) {
+        if depth > STACK_SIZE {
+            self.report(Violation::StackOverflow { depth: depth });
+        }
+    }
+
+    /// Checks the new value `i` of `I`
+    pub fn index(&mut self, i: u16) {
+        if i > MAX_ADDRESS {
+            self.report(Violation::IndexOutOfRange { i: i });
+        }
+    }
+
+    /// Checks a read of `size` bytes at `address`
+    pub fn read(&mut self, address: usize, size: usize) {
+        for address in address..address.saturating_add(size) {
+            if self.initialized.get(address) == Some(&false) {
+                self.report(Violation::UninitializedRead { address: address });
+            }
+        }
+    }
+
+    /// Checks a write of `size` bytes at `address`
+    pub fn write(&mut self, address: usize, size: usize) {
+        for address in address..address.saturating_add(size) {
+            if address < PROGRAM_START as usize {
+                self.report(Violation::ReservedWrite { address: address });
+            }
+            if self.executed.get(address) == Some(&true) {
+                self.report(Violation::SelfModifyingCode { address: address });
+            }
+            if let (Some(initialized), Some(modified)) = (self.initialized.get_mut(address), self.modified.get_mut(address)) {
+                *initialized = true;
+                *modified = true;
+            }
+        }
+    }
+
+    /// Returns the violations found since the last call
+    pub fn take_reports(&mut self) -> Vec<Chip8Error> {
+        mem::take(&mut self.reports)
+    }
+
+    /// Reports `violation` for the running instruction, unless it was already reported for its address
+    fn report(&mut self, violation: Violation) {
+        if self.reported.insert((self.pc, mem::discriminant(&violation))) {
+            self.reports.push(Chip8Error::StrictViolation {
+                pc: self.pc,
+                opcode: self.opcode,
+                violation: violation,
+            });
+        }
+    }
+}
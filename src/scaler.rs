@@ -0,0 +1,230 @@
+use crate::frame::Frame;
+
+/// Color of a lit pixel (white)
+pub const PIXEL_ON: u32 = 0xFFFFFF;
+/// Color of an unlit pixel (black)
+pub const PIXEL_OFF: u32 = 0x000000;
+
+#[derive(Clone, Copy, Debug)]
+/// Enum containing the different software upscaling filters that are supported
+///
+/// All the filters run on the CPU, the backends only have to stretch the resulting frame to the window
+pub enum ScalerType {
+    /// No filtering, the backend stretches the 64x32 frame itself
+    NEAREST,
+    /// AdvMAME2x / Scale2x, 2x upscaling
+    SCALE2X,
+    /// AdvMAME3x / Scale3x, 3x upscaling
+    SCALE3X,
+    /// Eric's Pixel Expansion, the original 2x algorithm Scale2x is based on
+    EPX,
+    /// Simplified xBR, 4x upscaling blending the pixels along diagonal edges
+    XBR,
+}
+
+impl ScalerType {
+    /// Converts the binary screen (0 or 1) of size `width`x`height` to a color frame and upscales it with the selected filter
+    ///
+    /// The returned frame is N times wider and higher, N being the filter factor
+    pub fn scale(&self, pixels: &[u8], width: usize, height: usize) -> Frame {
+        let colored = color_screen(pixels, width, height);
+
+        match self {
+            ScalerType::NEAREST => colored,
+            ScalerType::SCALE2X => scale2x(&colored),
+            ScalerType::SCALE3X => scale3x(&colored),
+            ScalerType::EPX => epx(&colored),
+            ScalerType::XBR => xbr(&colored),
+        }
+    }
+}
+
+/// Converts the binary screen (0 or 1) to a color frame (black / white)
+fn color_screen(pixels: &[u8], width: usize, height: usize) -> Frame {
+    Frame {
+        pixels: pixels.iter().map(|p| {
+            if *p == 1 {
+                PIXEL_ON
+            }
+            else {
+                PIXEL_OFF
+            }
+        }).collect(),
+        width: width,
+        height: height,
+    }
+}
+
+/// Iterates over the pixels of `image`, returning their coordinates and color
+fn coords(image: &Frame) -> impl Iterator<Item = (usize, usize, u32)> + '_ {
+    image.pixels.iter().enumerate().map(move |(i, &p)| (i % image.width, i / image.width, p))
+}
+
+/// Scale2x, see https://www.scale2x.it/algorithm
+///
+///   A      E0 E1
+/// C P B => E2 E3
+///   D
+fn scale2x(image: &Frame) -> Frame {
+    let mut out = Frame::new(image.width * 2, image.height * 2);
+
+    for (x, y, p) in coords(image) {
+        let (xi, yi) = (x as isize, y as isize);
+        let a = image.get(xi, yi - 1);
+        let b = image.get(xi + 1, yi);
+        let c = image.get(xi - 1, yi);
+        let d = image.get(xi, yi + 1);
+
+        let (ox, oy) = (x * 2, y * 2);
+        out.set(ox, oy, if c == a && c != d && a != b { a } else { p });
+        out.set(ox + 1, oy, if a == b && a != c && b != d { b } else { p });
+        out.set(ox, oy + 1, if d == c && d != b && c != a { c } else { p });
+        out.set(ox + 1, oy + 1, if b == d && b != a && d != c { d } else { p });
+    }
+
+    out
+}
+
+/// Scale3x, see https://www.scale2x.it/algorithm
+///
+/// A B C    E0 E1 E2
+/// D E F => E3 E4 E5
+/// G H I    E6 E7 E8
+fn scale3x(image: &Frame) -> Frame {
+    let mut out = Frame::new(image.width * 3, image.height * 3);
+
+    for (x, y, e) in coords(image) {
+        let (xi, yi) = (x as isize, y as isize);
+        let a = image.get(xi - 1, yi - 1);
+        let b = image.get(xi, yi - 1);
+        let c = image.get(xi + 1, yi - 1);
+        let d = image.get(xi - 1, yi);
+        let f = image.get(xi + 1, yi);
+        let g = image.get(xi - 1, yi + 1);
+        let h = image.get(xi, yi + 1);
+        let i = image.get(xi + 1, yi + 1);
+
+        let block = [
+            if d == b && b != f && d != h { d } else { e },
+            if (d == b && b != f && d != h && e != c) || (b == f && b != d && f != h && e != a) { b } else { e },
+            if b == f && b != d && f != h { f } else { e },
+            if (d == b && b != f && d != h && e != g) || (d == h && d != b && h != f && e != a) { d } else { e },
+            e,
+            if (b == f && b != d && f != h && e != i) || (h == f && d != h && b != f && e != c) { f } else { e },
+            if d == h && d != b && h != f { d } else { e },
+            if (d == h && d != b && h != f && e != i) || (h == f && d != h && b != f && e != g) { h } else { e },
+            if h == f && d != h && b != f { f } else { e },
+        ];
+
+        for (n, &color) in block.iter().enumerate() {
+            out.set(x * 3 + n % 3, y * 3 + n / 3, color);
+        }
+    }
+
+    out
+}
+
+/// EPX (Eric's Pixel Expansion)
+///
+///   A      1 2
+/// C P B => 3 4
+///   D
+fn epx(image: &Frame) -> Frame {
+    let mut out = Frame::new(image.width * 2, image.height * 2);
+
+    for (x, y, p) in coords(image) {
+        let (xi, yi) = (x as isize, y as isize);
+        let a = image.get(xi, yi - 1);
+        let b = image.get(xi + 1, yi);
+        let c = image.get(xi - 1, yi);
+        let d = image.get(xi, yi + 1);
+
+        let mut block = [p; 4];
+        if c == a {
+            block[0] = a;
+        }
+        if a == b {
+            block[1] = b;
+        }
+        if d == c {
+            block[2] = c;
+        }
+        if b == d {
+            block[3] = d;
+        }
+
+        // If 3 or more of the neighbours are identical, we keep the original pixel
+        let neighbours = [a, b, c, d];
+        if neighbours.iter().any(|n| neighbours.iter().filter(|&m| m == n).count() >= 3) {
+            block = [p; 4];
+        }
+
+        let (ox, oy) = (x * 2, y * 2);
+        out.set(ox, oy, block[0]);
+        out.set(ox + 1, oy, block[1]);
+        out.set(ox, oy + 1, block[2]);
+        out.set(ox + 1, oy + 1, block[3]);
+    }
+
+    out
+}
+
+/// Simplified xBR
+///
+/// The edges are detected the same way Scale2x does it, but instead of copying the neighbour color to a whole
+/// corner, the corner of the 4x4 block is blended with the neighbour color along the diagonal.
+/// This gives smooth 45° edges instead of staircases.
+fn xbr(image: &Frame) -> Frame {
+    // Neighbour color weight for a sub-pixel, depending on its distance to the corner (x + y)
+    const CORNER_WEIGHTS: [f32; 3] = [1.0, 0.75, 0.25];
+
+    let mut out = Frame::new(image.width * 4, image.height * 4);
+
+    for (x, y, p) in coords(image) {
+        let (xi, yi) = (x as isize, y as isize);
+        let a = image.get(xi, yi - 1);
+        let b = image.get(xi + 1, yi);
+        let c = image.get(xi - 1, yi);
+        let d = image.get(xi, yi + 1);
+
+        // Blended neighbour color of each corner (top-left, top-right, bottom-left, bottom-right)
+        let corners = [
+            if c == a && c != d && a != b { Some(a) } else { None },
+            if a == b && a != c && b != d { Some(b) } else { None },
+            if d == c && d != b && c != a { Some(c) } else { None },
+            if b == d && b != a && d != c { Some(d) } else { None },
+        ];
+
+        for sub_y in 0..4 {
+            for sub_x in 0..4 {
+                // Corner of the block this sub-pixel belongs to, and its distance to that corner
+                let (corner, distance) = match (sub_x < 2, sub_y < 2) {
+                    (true, true) => (0, sub_x + sub_y),
+                    (false, true) => (1, (3 - sub_x) + sub_y),
+                    (true, false) => (2, sub_x + (3 - sub_y)),
+                    (false, false) => (3, (3 - sub_x) + (3 - sub_y)),
+                };
+
+                let color = match (corners[corner], CORNER_WEIGHTS.get(distance)) {
+                    (Some(n), Some(&weight)) => blend(p, n, weight),
+                    _ => p,
+                };
+                out.set(x * 4 + sub_x, y * 4 + sub_y, color);
+            }
+        }
+    }
+
+    out
+}
+
+/// Blends the colors `from` and `to`, `weight` being the proportion of `to` in the result
+fn blend(from: u32, to: u32, weight: f32) -> u32 {
+    let mut result = 0;
+    for shift in [0, 8, 16].iter() {
+        let f = ((from >> shift) & 0xFF) as f32;
+        let t = ((to >> shift) & 0xFF) as f32;
+        let channel = (f + (t - f) * weight).round() as u32;
+        result |= channel << shift;
+    }
+    result
+}
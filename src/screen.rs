@@ -1,44 +1,65 @@
-use std::error::Error;
+use crate::error::Chip8Error;
 
 /// Width of the screen
 pub const WIDTH: usize = 64;
 /// Height of the screen
 pub const HEIGHT: usize = 32;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 /// Struct containing all the pixels of the screen
 pub struct Screen {
-    /// Vector containing vectors. Each of these vectors represent a row of the screen.
-    pub pixels: Vec<Vec<u8>>,
+    /// Contiguous buffer of `WIDTH` * `HEIGHT` pixels, row after row. 1 if the pixel is lit, 0 otherwise
+    pub pixels: Vec<u8>,
+    /// Whether the pixels changed since the last time the screen was presented
+    dirty: bool,
+}
+
+impl Default for Screen {
+    fn default() -> Self {
+        Screen::new()
+    }
 }
 
 impl Screen {
     /// Creates a new `Screen` object
     pub fn new() -> Self {
         Screen {
-            pixels: vec![vec![0; WIDTH]; HEIGHT],
+            pixels: vec![0; WIDTH * HEIGHT],
+            // The first frame always has to be presented
+            dirty: true,
         }
     }
 
+    /// Returns whether the pixels changed since the last call, and resets the dirty flag
+    pub fn take_dirty(&mut self) -> bool {
+        let dirty = self.dirty;
+        self.dirty = false;
+        dirty
+    }
+
     /// Draws the sprite `byte` to the screen at coordinates (`x`,`y`)
     /// For a sprite data byte, a bit set to one corresponds to a colored pixel (=white).
     /// Contrastingly, a bit set to zero corresponds to a transparent pixel (=black)
     ///
     /// Returns whether the drawing erased an existing byte so the CPU can set VF accordingly (1 if erased, 0 if not)
-    pub fn draw_byte(&mut self, x: u8, y: u8, mut byte: u8) -> Result<bool, Box<dyn Error>> {
+    pub fn draw_byte(&mut self, x: u8, y: u8, mut byte: u8) -> Result<bool, Chip8Error> {
         let mut erased = false;
 
         let x= (x % WIDTH as u8) as usize;
         let y = (y % HEIGHT as u8) as usize;
 
+        if byte != 0 {
+            self.dirty = true;
+        }
+
         for i in 0..8 {
-            let coord_x = (x+i) % WIDTH;
-            let prev_value = self.pixels[y][coord_x];
+            let index = y * WIDTH + (x+i) % WIDTH;
+            let prev_value = self.pixels[index];
             let bit = byte >> 7;
 
-            self.pixels[y][coord_x] ^= bit;
+            self.pixels[index] ^= bit;
 
-            if prev_value == 1 && self.pixels[y][coord_x] == 0 {
+            if prev_value == 1 && self.pixels[index] == 0 {
                 erased = true;
             }
 
@@ -50,17 +71,16 @@ impl Screen {
 
     /// Clears the screen, resetting all pixels to 0
     pub fn clear(&mut self) {
-        for row in self.pixels.iter_mut() {
-            for p in row.iter_mut() {
-                *p = 0;
-            }
+        for p in self.pixels.iter_mut() {
+            *p = 0;
         }
+        self.dirty = true;
     }
 
     #[allow(dead_code)]
     /// Displays the screen on the terminal, mainly for debug purpose
     pub fn terminal_display(&self) {
-        for row in self.pixels.iter() {
+        for row in self.pixels.chunks(WIDTH) {
             for &col in row.iter() {
                 if col == 1 {
                     print!("# ");
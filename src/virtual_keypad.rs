@@ -0,0 +1,117 @@
+use crate::frame::Frame;
+use crate::keypad::{Keypad, KeyEvent, KEYBOARD_SIZE};
+use crate::keypad_view::{self, draw_keypad};
+
+use std::time::Instant;
+
+/// Background of the panel around the keypad
+const PANEL_COLOR: u32 = 0x101010;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+/// Where the clickable keypad is drawn
+pub enum VirtualKeypadMode {
+    /// Not drawn
+    NONE,
+    /// On the right of the game
+    SIDE,
+    /// Under the game
+    BOTTOM,
+}
+
+#[derive(Debug)]
+/// Clickable keypad drawn in a panel beside or under the game, pressed with the mouse or by touch
+///
+/// The window is enlarged to make room for a square panel as high as the game area
+pub struct VirtualKeypad {
+    /// Where the keypad is drawn
+    mode: VirtualKeypadMode,
+    /// Size of the game area, in pixels
+    game_size: (usize, usize),
+    /// Keys currently pressed on the virtual keypad
+    pressed: [bool; KEYBOARD_SIZE],
+}
+
+impl VirtualKeypad {
+    /// Creates a new `VirtualKeypad` object for a game area of `game_width` x `game_height` pixels
+    pub fn new(mode: VirtualKeypadMode, game_width: usize, game_height: usize) -> Self {
+        VirtualKeypad {
+            mode: mode,
+            game_size: (game_width, game_height),
+            pressed: [false; KEYBOARD_SIZE],
+        }
+    }
+
+    /// Returns whether the keypad is drawn
+    pub fn is_enabled(&self) -> bool {
+        self.mode != VirtualKeypadMode::NONE
+    }
+
+    /// Returns the size of the window: the game area and the panel
+    pub fn window_size(&self) -> (usize, usize) {
+        let (width, height) = self.game_size;
+        match self.mode {
+            VirtualKeypadMode::NONE => (width, height),
+            VirtualKeypadMode::SIDE => (width + height, height),
+            VirtualKeypadMode::BOTTOM => (width, 2 * height),
+        }
+    }
+
+    /// Returns the position of the keypad in the window and its scale
+    fn layout(&self) -> (usize, usize, usize) {
+        let (width, height) = self.game_size;
+        // The keypad fills the panel, with a small margin
+        let scale = (height / (keypad_view::keypad_size(1) + 4)).max(1);
+        let size = keypad_view::keypad_size(scale);
+        let (panel_x, panel_y, panel_width) = match self.mode {
+            VirtualKeypadMode::BOTTOM => (0, height, width),
+            _ => (width, 0, height),
+        };
+        let x = panel_x + panel_width.saturating_sub(size) / 2;
+        let y = panel_y + height.saturating_sub(size) / 2;
+        (x, y, scale)
+    }
+
+    /// Presses and releases the keys under the `pointers`, given in window coordinates from 0.0 to 1.0.
+    /// Pushes an event into `events` for each key whose virtual state changed, so the physical keyboard
+    /// keeps working alongside
+    pub fn update(&mut self, pointers: &[(f32, f32)], events: &mut Vec<KeyEvent>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let (window_width, window_height) = self.window_size();
+        let (x, y, scale) = self.layout();
+        let mut pressed = [false; KEYBOARD_SIZE];
+        for &(pointer_x, pointer_y) in pointers {
+            let pointer_x = (pointer_x * window_width as f32) as usize;
+            let pointer_y = (pointer_y * window_height as f32) as usize;
+            if pointer_x < x || pointer_y < y {
+                continue;
+            }
+            if let Some(key) = keypad_view::key_at(pointer_x - x, pointer_y - y, scale) {
+                pressed[key as usize] = true;
+            }
+        }
+
+        let time = Instant::now();
+        for (key, (&now, &before)) in pressed.iter().zip(self.pressed.iter()).enumerate() {
+            if now != before {
+                events.push(KeyEvent { key: key as u8, pressed: now, time: time });
+            }
+        }
+        self.pressed = pressed;
+    }
+
+    /// Returns the frame to display: `game` stretched to the game area, and the keypad in the panel
+    pub fn compose(&self, game: &Frame, keypad: &Keypad) -> Frame {
+        let (window_width, window_height) = self.window_size();
+        let (width, height) = self.game_size;
+        let mut frame = Frame::new(window_width, window_height);
+        frame.fill_rect(0, 0, window_width, window_height, PANEL_COLOR);
+        frame.blit_scaled(game, 0, 0, width, height);
+
+        let (x, y, scale) = self.layout();
+        draw_keypad(&mut frame, x, y, keypad, scale);
+        frame
+    }
+}